@@ -0,0 +1,133 @@
+//! Pack user applications into an easy-fs image
+//!
+//! 用法：`easy-fs-fuse -s <源码目录> -t <ELF目录> [-o <镜像路径>]`
+//!
+//! 源码目录（通常是`user/src/bin`）中每个`.rs`文件对应一个应用，从ELF目录（通常是
+//! `user/target/riscv64gc-unknown-none-elf/release`）中取出同名的ELF文件，以应用名作为文件名
+//! 写入镜像的根目录。镜像默认放在ELF目录下的`fs.img`。
+
+use easy_fs::{BlockDevice, EasyFileSystem, BLOCK_SIZE};
+use std::fs::{read_dir, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::{Arc, Mutex};
+
+/// 镜像的总块数，16MiB
+const FS_IMG_BLOCKS: u32 = 32768;
+/// 索引节点位图的块数，最多4096个文件
+const INODE_BITMAP_BLOCKS: u32 = 1;
+
+/// 把宿主机上的普通文件当作块设备
+struct BlockFile(Mutex<File>);
+
+impl BlockDevice for BlockFile {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.read(buf).unwrap(), BLOCK_SIZE, "Not a complete block!");
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut file = self.0.lock().unwrap();
+        file.seek(SeekFrom::Start((block_id * BLOCK_SIZE) as u64))
+            .expect("Error when seeking!");
+        assert_eq!(file.write(buf).unwrap(), BLOCK_SIZE, "Not a complete block!");
+    }
+}
+
+/// 命令行参数
+struct Args {
+    src: PathBuf,
+    target: PathBuf,
+    output: PathBuf,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: easy-fs-fuse -s <source dir> -t <target dir> [-o <image>]");
+    exit(1);
+}
+
+fn parse_args() -> Args {
+    let mut src = None;
+    let mut target = None;
+    let mut output = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().map(PathBuf::from).unwrap_or_else(|| usage());
+        match arg.as_str() {
+            "-s" | "--source" => src = Some(value),
+            "-t" | "--target" => target = Some(value),
+            "-o" | "--output" => output = Some(value),
+            _ => usage(),
+        }
+    }
+    let (src, target) = match (src, target) {
+        (Some(src), Some(target)) => (src, target),
+        _ => usage(),
+    };
+    let output = output.unwrap_or_else(|| target.join("fs.img"));
+    Args {
+        src,
+        target,
+        output,
+    }
+}
+
+/// 源码目录中所有应用的名字，按名字排序
+fn app_names(src: &Path) -> std::io::Result<Vec<String>> {
+    let mut apps: Vec<String> = read_dir(src)?
+        .filter_map(|dir_entry| {
+            let path = dir_entry.ok()?.path();
+            if path.extension()? != "rs" {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_string())
+        })
+        .collect();
+    apps.sort();
+    Ok(apps)
+}
+
+fn main() {
+    let args = parse_args();
+    if let Err(err) = easy_fs_pack(&args) {
+        eprintln!("easy-fs-fuse: {}", err);
+        exit(1);
+    }
+}
+
+fn easy_fs_pack(args: &Args) -> std::io::Result<()> {
+    let block_file = Arc::new(BlockFile(Mutex::new({
+        let f = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&args.output)?;
+        f.set_len(FS_IMG_BLOCKS as u64 * BLOCK_SIZE as u64)?;
+        f
+    })));
+    let efs = EasyFileSystem::create(block_file, FS_IMG_BLOCKS, INODE_BITMAP_BLOCKS);
+    let root_inode = EasyFileSystem::root_inode(&efs);
+    for app in app_names(&args.src)? {
+        let mut all_data: Vec<u8> = Vec::new();
+        File::open(args.target.join(&app))?.read_to_end(&mut all_data)?;
+        let inode = root_inode.create(app.as_str()).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("cannot create {} in the image", app),
+            )
+        })?;
+        if inode.write_at(0, all_data.as_slice()) != all_data.len() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("no space left for {}", app),
+            ));
+        }
+        println!("{}: {} bytes", app, all_data.len());
+    }
+    println!("packed into {}", args.output.display());
+    Ok(())
+}
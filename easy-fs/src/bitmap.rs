@@ -0,0 +1,75 @@
+//! 索引节点和数据块的分配位图
+
+use super::block_cache::get_block_cache;
+use super::{BlockDevice, BLOCK_SIZE};
+use alloc::sync::Arc;
+
+/// 一个位图块看作64个u64
+type BitmapBlock = [u64; BLOCK_SIZE / 8];
+
+/// 每个位图块管理的位数
+const BLOCK_BITS: usize = BLOCK_SIZE * 8;
+
+/// 从`start_block_id`开始连续`blocks`块组成的位图，1代表已分配
+pub struct Bitmap {
+    start_block_id: usize,
+    blocks: usize,
+}
+
+/// 把位号拆成（位图内第几块，块内第几个u64，u64内第几位）
+fn decomposition(mut bit: usize) -> (usize, usize, usize) {
+    let block_pos = bit / BLOCK_BITS;
+    bit %= BLOCK_BITS;
+    (block_pos, bit / 64, bit % 64)
+}
+
+impl Bitmap {
+    /// 位图所在的块区间
+    pub fn new(start_block_id: usize, blocks: usize) -> Self {
+        Self {
+            start_block_id,
+            blocks,
+        }
+    }
+
+    /// 分配一位，返回它的位号，全部用完时返回None
+    pub fn alloc(&self, block_device: &Arc<dyn BlockDevice>) -> Option<usize> {
+        for block_id in 0..self.blocks {
+            let pos = get_block_cache(block_id + self.start_block_id, Arc::clone(block_device))
+                .lock()
+                .modify(0, |bitmap_block: &mut BitmapBlock| {
+                    let (bits64_pos, inner_pos) = bitmap_block
+                        .iter()
+                        .enumerate()
+                        .find(|(_, bits64)| **bits64 != u64::MAX)
+                        .map(|(bits64_pos, bits64)| (bits64_pos, bits64.trailing_ones() as usize))?;
+                    bitmap_block[bits64_pos] |= 1u64 << inner_pos;
+                    Some(block_id * BLOCK_BITS + bits64_pos * 64 + inner_pos)
+                });
+            if pos.is_some() {
+                return pos;
+            }
+        }
+        None
+    }
+
+    /// 回收第`bit`位
+    pub fn dealloc(&self, block_device: &Arc<dyn BlockDevice>, bit: usize) {
+        let (block_pos, bits64_pos, inner_pos) = decomposition(bit);
+        get_block_cache(block_pos + self.start_block_id, Arc::clone(block_device))
+            .lock()
+            .modify(0, |bitmap_block: &mut BitmapBlock| {
+                assert!(
+                    bitmap_block[bits64_pos] & (1u64 << inner_pos) != 0,
+                    "bit {} has not been allocated!",
+                    bit
+                );
+                bitmap_block[bits64_pos] -= 1u64 << inner_pos;
+            });
+    }
+
+    /// 位图最多能管理的位数
+    pub fn maximum(&self) -> usize {
+        self.blocks * BLOCK_BITS
+    }
+}
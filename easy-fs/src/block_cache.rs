@@ -0,0 +1,161 @@
+//! 块缓存
+//!
+//! 对块的所有访问都经过缓存：读取时整块读入内存，修改后标记为脏，在被替换或者
+//! [`block_cache_sync_all`]时写回。
+
+use super::{BlockDevice, BLOCK_SIZE};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use lazy_static::*;
+use spin::Mutex;
+
+/// 同时缓存的块数
+const BLOCK_CACHE_SIZE: usize = 16;
+
+/// 块的内容，按8字节对齐，这样可以直接当作磁盘上的结构体访问
+#[repr(C, align(8))]
+struct BlockData([u8; BLOCK_SIZE]);
+
+/// 一个块在内存中的副本
+pub struct BlockCache {
+    cache: BlockData,
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+    // 被修改过，需要写回
+    modified: bool,
+}
+
+impl BlockCache {
+    /// 从设备读入第`block_id`块
+    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
+        let mut cache = BlockData([0; BLOCK_SIZE]);
+        block_device.read_block(block_id, &mut cache.0);
+        Self {
+            cache,
+            block_id,
+            block_device,
+            modified: false,
+        }
+    }
+
+    fn addr_of_offset(&self, offset: usize) -> usize {
+        &self.cache.0[offset] as *const _ as usize
+    }
+
+    /// 把块内偏移`offset`处的内容当作`T`读取
+    pub fn get_ref<T>(&self, offset: usize) -> &T
+    where
+        T: Sized,
+    {
+        let type_size = core::mem::size_of::<T>();
+        assert!(offset + type_size <= BLOCK_SIZE);
+        let addr = self.addr_of_offset(offset);
+        assert_eq!(addr % core::mem::align_of::<T>(), 0);
+        unsafe { &*(addr as *const T) }
+    }
+
+    /// 把块内偏移`offset`处的内容当作`T`修改，块被标记为脏
+    pub fn get_mut<T>(&mut self, offset: usize) -> &mut T
+    where
+        T: Sized,
+    {
+        let type_size = core::mem::size_of::<T>();
+        assert!(offset + type_size <= BLOCK_SIZE);
+        self.modified = true;
+        let addr = self.addr_of_offset(offset);
+        assert_eq!(addr % core::mem::align_of::<T>(), 0);
+        unsafe { &mut *(addr as *mut T) }
+    }
+
+    /// 在块内偏移`offset`处的`T`上执行只读操作
+    pub fn read<T, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> V {
+        f(self.get_ref(offset))
+    }
+
+    /// 在块内偏移`offset`处的`T`上执行修改操作
+    pub fn modify<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
+        f(self.get_mut(offset))
+    }
+
+    /// 脏块写回设备
+    pub fn sync(&mut self) {
+        if self.modified {
+            self.modified = false;
+            self.block_device.write_block(self.block_id, &self.cache.0);
+        }
+    }
+}
+
+impl Drop for BlockCache {
+    fn drop(&mut self) {
+        self.sync()
+    }
+}
+
+/// 块缓存的集合，满了之后替换掉最早加入的、没有被其他地方持有的块
+pub struct BlockCacheManager {
+    queue: VecDeque<(usize, Arc<Mutex<BlockCache>>)>,
+}
+
+impl BlockCacheManager {
+    /// 空的缓存
+    pub fn new() -> Self {
+        Self {
+            queue: VecDeque::new(),
+        }
+    }
+
+    /// 取得第`block_id`块的缓存，不在缓存中时从设备读入
+    pub fn get_block_cache(
+        &mut self,
+        block_id: usize,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Arc<Mutex<BlockCache>> {
+        if let Some((_, cache)) = self.queue.iter().find(|(id, _)| *id == block_id) {
+            return Arc::clone(cache);
+        }
+        if self.queue.len() == BLOCK_CACHE_SIZE {
+            // 引用计数为1说明只有缓存自己持有它，可以替换
+            let idx = self
+                .queue
+                .iter()
+                .position(|(_, cache)| Arc::strong_count(cache) == 1)
+                .expect("Run out of BlockCache!");
+            // drop时写回
+            self.queue.remove(idx);
+        }
+        let block_cache = Arc::new(Mutex::new(BlockCache::new(block_id, block_device)));
+        self.queue.push_back((block_id, Arc::clone(&block_cache)));
+        block_cache
+    }
+}
+
+impl Default for BlockCacheManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+lazy_static! {
+    /// 全局的块缓存
+    pub static ref BLOCK_CACHE_MANAGER: Mutex<BlockCacheManager> =
+        Mutex::new(BlockCacheManager::new());
+}
+
+/// 取得`block_device`上第`block_id`块的缓存
+pub fn get_block_cache(
+    block_id: usize,
+    block_device: Arc<dyn BlockDevice>,
+) -> Arc<Mutex<BlockCache>> {
+    BLOCK_CACHE_MANAGER
+        .lock()
+        .get_block_cache(block_id, block_device)
+}
+
+/// 把所有脏块写回设备
+pub fn block_cache_sync_all() {
+    let manager = BLOCK_CACHE_MANAGER.lock();
+    for (_, cache) in manager.queue.iter() {
+        cache.lock().sync();
+    }
+}
@@ -0,0 +1,9 @@
+use core::any::Any;
+
+/// 以块为单位读写的存储设备，块大小为[`BLOCK_SIZE`](crate::BLOCK_SIZE)
+pub trait BlockDevice: Send + Sync + Any {
+    /// 把第`block_id`块读到`buf`中
+    fn read_block(&self, block_id: usize, buf: &mut [u8]);
+    /// 把`buf`写到第`block_id`块
+    fn write_block(&self, block_id: usize, buf: &[u8]);
+}
@@ -0,0 +1,170 @@
+use super::bitmap::Bitmap;
+use super::block_cache::{block_cache_sync_all, get_block_cache};
+use super::layout::{DiskInode, DiskInodeType, SuperBlock};
+use super::vfs::Inode;
+use super::{BlockDevice, BLOCK_SIZE};
+use alloc::sync::Arc;
+use spin::Mutex;
+
+/// 一个数据块位图块加上它管理的数据块
+const DATA_BITMAP_GROUP: u32 = BLOCK_SIZE as u32 * 8 + 1;
+
+/// 根目录的索引节点编号
+const ROOT_INODE_ID: u32 = 0;
+
+/// 打开的文件系统，记录各个区域的位置，负责索引节点和数据块的分配
+pub struct EasyFileSystem {
+    /// 文件系统所在的块设备
+    pub block_device: Arc<dyn BlockDevice>,
+    inode_bitmap: Bitmap,
+    data_bitmap: Bitmap,
+    inode_area_start_block: u32,
+    data_area_start_block: u32,
+    data_area_blocks: u32,
+}
+
+type DataBlock = [u8; BLOCK_SIZE];
+
+impl EasyFileSystem {
+    /// 在`block_device`的前`total_blocks`块上创建一个只有空的根目录的文件系统
+    ///
+    /// 索引节点位图占`inode_bitmap_blocks`块，每块可以管理4096个索引节点，剩下的空间
+    /// 都分给数据块
+    pub fn create(
+        block_device: Arc<dyn BlockDevice>,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+    ) -> Arc<Mutex<Self>> {
+        let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
+        let inode_num = inode_bitmap.maximum();
+        let inode_area_blocks = ((inode_num * core::mem::size_of::<DiskInode>() + BLOCK_SIZE - 1)
+            / BLOCK_SIZE) as u32;
+        let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
+        let data_total_blocks = total_blocks - 1 - inode_total_blocks;
+        // 每个位图块管理4096个数据块，位图块数向上取整
+        let data_bitmap_blocks = (data_total_blocks + DATA_BITMAP_GROUP - 1) / DATA_BITMAP_GROUP;
+        let data_area_blocks = data_total_blocks - data_bitmap_blocks;
+        let data_bitmap = Bitmap::new(
+            (1 + inode_total_blocks) as usize,
+            data_bitmap_blocks as usize,
+        );
+        let mut efs = Self {
+            block_device: Arc::clone(&block_device),
+            inode_bitmap,
+            data_bitmap,
+            inode_area_start_block: 1 + inode_bitmap_blocks,
+            data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            data_area_blocks,
+        };
+        // 清空所有块
+        for i in 0..total_blocks {
+            get_block_cache(i as usize, Arc::clone(&block_device))
+                .lock()
+                .modify(0, |data_block: &mut DataBlock| {
+                    data_block.iter_mut().for_each(|byte| *byte = 0);
+                });
+        }
+        get_block_cache(0, Arc::clone(&block_device)).lock().modify(
+            0,
+            |super_block: &mut SuperBlock| {
+                super_block.initialize(
+                    total_blocks,
+                    inode_bitmap_blocks,
+                    inode_area_blocks,
+                    data_bitmap_blocks,
+                    data_area_blocks,
+                );
+            },
+        );
+        // 第一个索引节点是根目录
+        assert_eq!(efs.alloc_inode(), Some(ROOT_INODE_ID));
+        let (root_inode_block_id, root_inode_offset) = efs.get_disk_inode_pos(ROOT_INODE_ID);
+        get_block_cache(root_inode_block_id as usize, Arc::clone(&block_device))
+            .lock()
+            .modify(root_inode_offset, |disk_inode: &mut DiskInode| {
+                disk_inode.initialize(DiskInodeType::Directory);
+            });
+        block_cache_sync_all();
+        Arc::new(Mutex::new(efs))
+    }
+
+    /// 打开`block_device`上已有的文件系统，超级块无效时返回None
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Option<Arc<Mutex<Self>>> {
+        get_block_cache(0, Arc::clone(&block_device))
+            .lock()
+            .read(0, |super_block: &SuperBlock| {
+                if !super_block.is_valid() {
+                    return None;
+                }
+                let inode_total_blocks =
+                    super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
+                let efs = Self {
+                    block_device: Arc::clone(&block_device),
+                    inode_bitmap: Bitmap::new(1, super_block.inode_bitmap_blocks as usize),
+                    data_bitmap: Bitmap::new(
+                        (1 + inode_total_blocks) as usize,
+                        super_block.data_bitmap_blocks as usize,
+                    ),
+                    inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
+                    data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    data_area_blocks: super_block.data_area_blocks,
+                };
+                Some(Arc::new(Mutex::new(efs)))
+            })
+    }
+
+    /// 根目录
+    pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
+        let block_device = Arc::clone(&efs.lock().block_device);
+        let (block_id, block_offset) = efs.lock().get_disk_inode_pos(ROOT_INODE_ID);
+        Inode::new(block_id, block_offset, Arc::clone(efs), block_device)
+    }
+
+    /// 第`inode_id`个索引节点所在的块号和块内偏移
+    pub fn get_disk_inode_pos(&self, inode_id: u32) -> (u32, usize) {
+        let inode_size = core::mem::size_of::<DiskInode>();
+        let inodes_per_block = (BLOCK_SIZE / inode_size) as u32;
+        let block_id = self.inode_area_start_block + inode_id / inodes_per_block;
+        (
+            block_id,
+            (inode_id % inodes_per_block) as usize * inode_size,
+        )
+    }
+
+    /// 分配一个索引节点，返回它的编号
+    pub fn alloc_inode(&mut self) -> Option<u32> {
+        self.inode_bitmap
+            .alloc(&self.block_device)
+            .map(|id| id as u32)
+    }
+
+    /// 回收编号为`inode_id`的索引节点
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap
+            .dealloc(&self.block_device, inode_id as usize);
+    }
+
+    /// 分配一个数据块，返回它的块号
+    pub fn alloc_data(&mut self) -> Option<u32> {
+        let id = self.data_bitmap.alloc(&self.block_device)? as u32;
+        // 最后一个位图块可能管理不满4096块，超出数据区的位不能用
+        if id >= self.data_area_blocks {
+            self.data_bitmap.dealloc(&self.block_device, id as usize);
+            return None;
+        }
+        Some(id + self.data_area_start_block)
+    }
+
+    /// 回收块号为`block_id`的数据块，内容被清零
+    pub fn dealloc_data(&mut self, block_id: u32) {
+        get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| {
+                data_block.iter_mut().for_each(|byte| *byte = 0);
+            });
+        self.data_bitmap.dealloc(
+            &self.block_device,
+            (block_id - self.data_area_start_block) as usize,
+        );
+    }
+}
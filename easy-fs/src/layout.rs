@@ -0,0 +1,444 @@
+//! 磁盘上的数据结构
+
+use super::block_cache::get_block_cache;
+use super::{BlockDevice, BLOCK_SIZE};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Formatter, Result};
+
+/// 超级块中的魔数，用来判断磁盘上是不是easy-fs
+const EFS_MAGIC: u32 = 0x3b80_0001;
+/// 索引节点中直接索引的数量
+const INODE_DIRECT_COUNT: usize = 28;
+/// 文件名的最大长度，目录项中还要留一个字节存放结尾的'\0'
+pub const NAME_LENGTH_LIMIT: usize = 27;
+/// 一个间接索引块中块号的数量
+const INODE_INDIRECT1_COUNT: usize = BLOCK_SIZE / 4;
+/// 二级间接索引能找到的块数
+const INODE_INDIRECT2_COUNT: usize = INODE_INDIRECT1_COUNT * INODE_INDIRECT1_COUNT;
+// 文件内块号的分界：[0, DIRECT_BOUND)用直接索引，[DIRECT_BOUND, INDIRECT1_BOUND)用一级间接索引，
+// 剩下的用二级间接索引
+const DIRECT_BOUND: usize = INODE_DIRECT_COUNT;
+const INDIRECT1_BOUND: usize = DIRECT_BOUND + INODE_INDIRECT1_COUNT;
+const INDIRECT2_BOUND: usize = INDIRECT1_BOUND + INODE_INDIRECT2_COUNT;
+
+/// 超级块，位于第0块，记录各个区域的大小
+#[repr(C)]
+pub struct SuperBlock {
+    magic: u32,
+    /// 文件系统占用的总块数
+    pub total_blocks: u32,
+    /// 索引节点位图的块数
+    pub inode_bitmap_blocks: u32,
+    /// 索引节点区的块数
+    pub inode_area_blocks: u32,
+    /// 数据块位图的块数
+    pub data_bitmap_blocks: u32,
+    /// 数据块区的块数
+    pub data_area_blocks: u32,
+}
+
+impl Debug for SuperBlock {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        f.debug_struct("SuperBlock")
+            .field("total_blocks", &self.total_blocks)
+            .field("inode_bitmap_blocks", &self.inode_bitmap_blocks)
+            .field("inode_area_blocks", &self.inode_area_blocks)
+            .field("data_bitmap_blocks", &self.data_bitmap_blocks)
+            .field("data_area_blocks", &self.data_area_blocks)
+            .finish()
+    }
+}
+
+impl SuperBlock {
+    /// 写入各个区域的大小和魔数
+    pub fn initialize(
+        &mut self,
+        total_blocks: u32,
+        inode_bitmap_blocks: u32,
+        inode_area_blocks: u32,
+        data_bitmap_blocks: u32,
+        data_area_blocks: u32,
+    ) {
+        *self = Self {
+            magic: EFS_MAGIC,
+            total_blocks,
+            inode_bitmap_blocks,
+            inode_area_blocks,
+            data_bitmap_blocks,
+            data_area_blocks,
+        }
+    }
+
+    /// 魔数是否正确
+    pub fn is_valid(&self) -> bool {
+        self.magic == EFS_MAGIC
+    }
+}
+
+/// 索引节点的类型
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+#[repr(u32)]
+pub enum DiskInodeType {
+    /// 普通文件
+    File,
+    /// 目录，内容是一组[`DirEntry`]
+    Directory,
+}
+
+/// 一个间接索引块
+type IndirectBlock = [u32; BLOCK_SIZE / 4];
+/// 一个数据块
+type DataBlock = [u8; BLOCK_SIZE];
+
+/// 磁盘上的索引节点，大小为128字节，一个块中放4个
+#[repr(C)]
+pub struct DiskInode {
+    /// 文件大小（字节）
+    pub size: u32,
+    /// 直接索引
+    pub direct: [u32; INODE_DIRECT_COUNT],
+    /// 一级间接索引块
+    pub indirect1: u32,
+    /// 二级间接索引块
+    pub indirect2: u32,
+    type_: DiskInodeType,
+}
+
+impl DiskInode {
+    /// 初始化为大小为0的`type_`类型索引节点，间接索引块在需要时才分配
+    pub fn initialize(&mut self, type_: DiskInodeType) {
+        self.size = 0;
+        self.direct.iter_mut().for_each(|v| *v = 0);
+        self.indirect1 = 0;
+        self.indirect2 = 0;
+        self.type_ = type_;
+    }
+
+    /// 是否是目录
+    pub fn is_dir(&self) -> bool {
+        self.type_ == DiskInodeType::Directory
+    }
+
+    /// 是否是普通文件
+    pub fn is_file(&self) -> bool {
+        self.type_ == DiskInodeType::File
+    }
+
+    /// 存放数据需要的块数
+    pub fn data_blocks(&self) -> u32 {
+        Self::_data_blocks(self.size)
+    }
+
+    fn _data_blocks(size: u32) -> u32 {
+        (size + BLOCK_SIZE as u32 - 1) / BLOCK_SIZE as u32
+    }
+
+    /// 大小为`size`的文件一共需要的块数，包括间接索引块
+    pub fn total_blocks(size: u32) -> u32 {
+        let data_blocks = Self::_data_blocks(size) as usize;
+        let mut total = data_blocks;
+        if data_blocks > DIRECT_BOUND {
+            total += 1;
+        }
+        if data_blocks > INDIRECT1_BOUND {
+            // 二级间接索引块本身，以及它下面的一级索引块
+            total += 1;
+            total += (data_blocks - INDIRECT1_BOUND + INODE_INDIRECT1_COUNT - 1)
+                / INODE_INDIRECT1_COUNT;
+        }
+        total as u32
+    }
+
+    /// 扩大到`new_size`需要新分配的块数
+    pub fn blocks_num_needed(&self, new_size: u32) -> u32 {
+        assert!(new_size >= self.size);
+        Self::total_blocks(new_size) - Self::total_blocks(self.size)
+    }
+
+    /// 文件内第`inner_id`块在磁盘上的块号
+    pub fn get_block_id(&self, inner_id: u32, block_device: &Arc<dyn BlockDevice>) -> u32 {
+        let inner_id = inner_id as usize;
+        if inner_id < DIRECT_BOUND {
+            self.direct[inner_id]
+        } else if inner_id < INDIRECT1_BOUND {
+            get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect_block: &IndirectBlock| {
+                    indirect_block[inner_id - DIRECT_BOUND]
+                })
+        } else {
+            assert!(inner_id < INDIRECT2_BOUND, "file too large");
+            let last = inner_id - INDIRECT1_BOUND;
+            let indirect1 = get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect2: &IndirectBlock| {
+                    indirect2[last / INODE_INDIRECT1_COUNT]
+                });
+            get_block_cache(indirect1 as usize, Arc::clone(block_device))
+                .lock()
+                .read(0, |indirect1: &IndirectBlock| {
+                    indirect1[last % INODE_INDIRECT1_COUNT]
+                })
+        }
+    }
+
+    /// 扩大到`new_size`，`new_blocks`是调用者分配好的块，数量由[`Self::blocks_num_needed`]得到
+    pub fn increase_size(
+        &mut self,
+        new_size: u32,
+        new_blocks: Vec<u32>,
+        block_device: &Arc<dyn BlockDevice>,
+    ) {
+        let mut current_blocks = self.data_blocks();
+        self.size = new_size;
+        let mut total_blocks = self.data_blocks();
+        let mut new_blocks = new_blocks.into_iter();
+        // 直接索引
+        while current_blocks < total_blocks.min(INODE_DIRECT_COUNT as u32) {
+            self.direct[current_blocks as usize] = new_blocks.next().unwrap();
+            current_blocks += 1;
+        }
+        // 一级间接索引
+        if total_blocks > INODE_DIRECT_COUNT as u32 {
+            if current_blocks == INODE_DIRECT_COUNT as u32 {
+                self.indirect1 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_DIRECT_COUNT as u32;
+            total_blocks -= INODE_DIRECT_COUNT as u32;
+        } else {
+            return;
+        }
+        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect1: &mut IndirectBlock| {
+                while current_blocks < total_blocks.min(INODE_INDIRECT1_COUNT as u32) {
+                    indirect1[current_blocks as usize] = new_blocks.next().unwrap();
+                    current_blocks += 1;
+                }
+            });
+        // 二级间接索引
+        if total_blocks > INODE_INDIRECT1_COUNT as u32 {
+            if current_blocks == INODE_INDIRECT1_COUNT as u32 {
+                self.indirect2 = new_blocks.next().unwrap();
+            }
+            current_blocks -= INODE_INDIRECT1_COUNT as u32;
+            total_blocks -= INODE_INDIRECT1_COUNT as u32;
+        } else {
+            return;
+        }
+        // 从(a0, b0)填到(a1, b1)，a是二级索引块中的下标，b是一级索引块中的下标
+        let mut a0 = current_blocks as usize / INODE_INDIRECT1_COUNT;
+        let mut b0 = current_blocks as usize % INODE_INDIRECT1_COUNT;
+        let a1 = total_blocks as usize / INODE_INDIRECT1_COUNT;
+        let b1 = total_blocks as usize % INODE_INDIRECT1_COUNT;
+        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+            .lock()
+            .modify(0, |indirect2: &mut IndirectBlock| {
+                while (a0 < a1) || (a0 == a1 && b0 < b1) {
+                    if b0 == 0 {
+                        indirect2[a0] = new_blocks.next().unwrap();
+                    }
+                    get_block_cache(indirect2[a0] as usize, Arc::clone(block_device))
+                        .lock()
+                        .modify(0, |indirect1: &mut IndirectBlock| {
+                            indirect1[b0] = new_blocks.next().unwrap();
+                        });
+                    b0 += 1;
+                    if b0 == INODE_INDIRECT1_COUNT {
+                        b0 = 0;
+                        a0 += 1;
+                    }
+                }
+            });
+    }
+
+    /// 把大小清零，返回所有需要回收的块，包括间接索引块
+    pub fn clear_size(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let mut v: Vec<u32> = Vec::new();
+        let mut data_blocks = self.data_blocks() as usize;
+        self.size = 0;
+        let mut current_blocks = 0usize;
+        // 直接索引
+        while current_blocks < data_blocks.min(INODE_DIRECT_COUNT) {
+            v.push(self.direct[current_blocks]);
+            self.direct[current_blocks] = 0;
+            current_blocks += 1;
+        }
+        // 一级间接索引
+        if data_blocks > INODE_DIRECT_COUNT {
+            v.push(self.indirect1);
+            data_blocks -= INODE_DIRECT_COUNT;
+            current_blocks = 0;
+        } else {
+            return v;
+        }
+        get_block_cache(self.indirect1 as usize, Arc::clone(block_device))
+            .lock()
+            .read(0, |indirect1: &IndirectBlock| {
+                while current_blocks < data_blocks.min(INODE_INDIRECT1_COUNT) {
+                    v.push(indirect1[current_blocks]);
+                    current_blocks += 1;
+                }
+            });
+        self.indirect1 = 0;
+        // 二级间接索引
+        if data_blocks > INODE_INDIRECT1_COUNT {
+            v.push(self.indirect2);
+            data_blocks -= INODE_INDIRECT1_COUNT;
+        } else {
+            return v;
+        }
+        assert!(data_blocks <= INODE_INDIRECT2_COUNT);
+        let a1 = data_blocks / INODE_INDIRECT1_COUNT;
+        let b1 = data_blocks % INODE_INDIRECT1_COUNT;
+        get_block_cache(self.indirect2 as usize, Arc::clone(block_device))
+            .lock()
+            .read(0, |indirect2: &IndirectBlock| {
+                // 完整的一级索引块
+                for entry in indirect2.iter().take(a1) {
+                    v.push(*entry);
+                    get_block_cache(*entry as usize, Arc::clone(block_device))
+                        .lock()
+                        .read(0, |indirect1: &IndirectBlock| {
+                            v.extend_from_slice(indirect1);
+                        });
+                }
+                // 最后一个不满的一级索引块
+                if b1 > 0 {
+                    v.push(indirect2[a1]);
+                    get_block_cache(indirect2[a1] as usize, Arc::clone(block_device))
+                        .lock()
+                        .read(0, |indirect1: &IndirectBlock| {
+                            v.extend_from_slice(&indirect1[..b1]);
+                        });
+                }
+            });
+        self.indirect2 = 0;
+        v
+    }
+
+    /// 从`offset`开始读到`buf`中，返回读到的字节数，超过文件末尾的部分不读
+    pub fn read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        if start >= end {
+            return 0;
+        }
+        let mut start_block = start / BLOCK_SIZE;
+        let mut read_size = 0usize;
+        loop {
+            // 当前块的结束位置
+            let end_current_block = ((start / BLOCK_SIZE + 1) * BLOCK_SIZE).min(end);
+            let block_read_size = end_current_block - start;
+            let dst = &mut buf[read_size..read_size + block_read_size];
+            get_block_cache(
+                self.get_block_id(start_block as u32, block_device) as usize,
+                Arc::clone(block_device),
+            )
+            .lock()
+            .read(0, |data_block: &DataBlock| {
+                let src = &data_block[start % BLOCK_SIZE..start % BLOCK_SIZE + block_read_size];
+                dst.copy_from_slice(src);
+            });
+            read_size += block_read_size;
+            if end_current_block == end {
+                break;
+            }
+            start_block += 1;
+            start = end_current_block;
+        }
+        read_size
+    }
+
+    /// 从`offset`开始写入`buf`，调用者需要先把文件扩大到足够的大小
+    pub fn write_at(
+        &mut self,
+        offset: usize,
+        buf: &[u8],
+        block_device: &Arc<dyn BlockDevice>,
+    ) -> usize {
+        let mut start = offset;
+        let end = (offset + buf.len()).min(self.size as usize);
+        let mut start_block = start / BLOCK_SIZE;
+        let mut write_size = 0usize;
+        while start < end {
+            let end_current_block = ((start / BLOCK_SIZE + 1) * BLOCK_SIZE).min(end);
+            let block_write_size = end_current_block - start;
+            get_block_cache(
+                self.get_block_id(start_block as u32, block_device) as usize,
+                Arc::clone(block_device),
+            )
+            .lock()
+            .modify(0, |data_block: &mut DataBlock| {
+                let src = &buf[write_size..write_size + block_write_size];
+                let dst =
+                    &mut data_block[start % BLOCK_SIZE..start % BLOCK_SIZE + block_write_size];
+                dst.copy_from_slice(src);
+            });
+            write_size += block_write_size;
+            start_block += 1;
+            start = end_current_block;
+        }
+        write_size
+    }
+}
+
+/// 目录项，大小为32字节
+#[repr(C)]
+pub struct DirEntry {
+    name: [u8; NAME_LENGTH_LIMIT + 1],
+    inode_number: u32,
+}
+
+/// 目录项的大小
+pub const DIRENT_SZ: usize = 32;
+
+impl DirEntry {
+    /// 空的目录项
+    pub fn empty() -> Self {
+        Self {
+            name: [0u8; NAME_LENGTH_LIMIT + 1],
+            inode_number: 0,
+        }
+    }
+
+    /// 名字为`name`、指向`inode_number`号索引节点的目录项，`name`过长时返回None
+    pub fn new(name: &str, inode_number: u32) -> Option<Self> {
+        if name.len() > NAME_LENGTH_LIMIT {
+            return None;
+        }
+        let mut bytes = [0u8; NAME_LENGTH_LIMIT + 1];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Some(Self {
+            name: bytes,
+            inode_number,
+        })
+    }
+
+    /// 作为字节序列读取
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self as *const _ as usize as *const u8, DIRENT_SZ) }
+    }
+
+    /// 作为字节序列写入
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self as *mut _ as usize as *mut u8, DIRENT_SZ) }
+    }
+
+    /// 文件名
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(self.name.len());
+        core::str::from_utf8(&self.name[..len]).unwrap()
+    }
+
+    /// 指向的索引节点编号
+    pub fn inode_number(&self) -> u32 {
+        self.inode_number
+    }
+}
@@ -0,0 +1,35 @@
+//! A simple on-disk file system
+//!
+//! easy-fs不依赖std，既可以在内核中通过virtio-blk使用，也可以在宿主机上通过普通文件使用
+//! （见`easy-fs-fuse`，它把用户程序打包成`fs.img`）。磁盘布局从前到后依次是：
+//!
+//! - 超级块（[`SuperBlock`]），占第0块
+//! - 索引节点位图
+//! - 索引节点区，每个[`DiskInode`]占128字节
+//! - 数据块位图
+//! - 数据块区
+//!
+//! 文件的数据块由索引节点中的直接索引、一级间接索引和二级间接索引找到。目前只有一个
+//! 根目录，所有文件都直接放在根目录下。
+//!
+//! 所有块的读写都经过[`block_cache`]，上层通过[`EasyFileSystem`]和[`Inode`]使用文件系统。
+
+#![no_std]
+
+extern crate alloc;
+
+mod bitmap;
+mod block_cache;
+mod block_dev;
+mod efs;
+mod layout;
+mod vfs;
+
+/// 块大小（字节）
+pub const BLOCK_SIZE: usize = 512;
+
+pub use block_cache::block_cache_sync_all;
+pub use block_dev::BlockDevice;
+pub use efs::EasyFileSystem;
+pub use layout::{DiskInode, DiskInodeType, SuperBlock};
+pub use vfs::Inode;
@@ -0,0 +1,250 @@
+use super::block_cache::{block_cache_sync_all, get_block_cache};
+use super::layout::{DirEntry, DiskInode, DiskInodeType, DIRENT_SZ, NAME_LENGTH_LIMIT};
+use super::{BlockDevice, EasyFileSystem};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use spin::{Mutex, MutexGuard};
+
+/// 内存中的索引节点，指向磁盘上的一个[`DiskInode`]
+///
+/// 所有操作都先锁住整个文件系统，所以多个`Inode`可以同时指向同一个文件
+pub struct Inode {
+    block_id: usize,
+    block_offset: usize,
+    fs: Arc<Mutex<EasyFileSystem>>,
+    block_device: Arc<dyn BlockDevice>,
+}
+
+impl Inode {
+    /// 位于第`block_id`块、块内偏移`block_offset`的索引节点
+    pub fn new(
+        block_id: u32,
+        block_offset: usize,
+        fs: Arc<Mutex<EasyFileSystem>>,
+        block_device: Arc<dyn BlockDevice>,
+    ) -> Self {
+        Self {
+            block_id: block_id as usize,
+            block_offset,
+            fs,
+            block_device,
+        }
+    }
+
+    fn read_disk_inode<V>(&self, f: impl FnOnce(&DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, Arc::clone(&self.block_device))
+            .lock()
+            .read(self.block_offset, f)
+    }
+
+    fn modify_disk_inode<V>(&self, f: impl FnOnce(&mut DiskInode) -> V) -> V {
+        get_block_cache(self.block_id, Arc::clone(&self.block_device))
+            .lock()
+            .modify(self.block_offset, f)
+    }
+
+    /// 在目录`disk_inode`中查找`name`，返回索引节点编号
+    fn find_inode_id(&self, name: &str, disk_inode: &DiskInode) -> Option<u32> {
+        assert!(disk_inode.is_dir());
+        let file_count = disk_inode.size as usize / DIRENT_SZ;
+        let mut dirent = DirEntry::empty();
+        for i in 0..file_count {
+            assert_eq!(
+                disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device),
+                DIRENT_SZ,
+            );
+            if dirent.name() == name {
+                return Some(dirent.inode_number());
+            }
+        }
+        None
+    }
+
+    /// 编号为`inode_id`的索引节点
+    fn inode_of(&self, fs: &EasyFileSystem, inode_id: u32) -> Arc<Inode> {
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        Arc::new(Self::new(
+            block_id,
+            block_offset,
+            Arc::clone(&self.fs),
+            Arc::clone(&self.block_device),
+        ))
+    }
+
+    /// 在当前目录下按名字查找文件
+    pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
+        let fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))
+            .map(|inode_id| self.inode_of(&fs, inode_id))
+    }
+
+    /// 把`disk_inode`扩大到`new_size`，磁盘空间不足时返回false，大小不变
+    fn increase_size(
+        &self,
+        new_size: u32,
+        disk_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) -> bool {
+        if new_size <= disk_inode.size {
+            return true;
+        }
+        let blocks_needed = disk_inode.blocks_num_needed(new_size);
+        let mut new_blocks: Vec<u32> = Vec::new();
+        for _ in 0..blocks_needed {
+            match fs.alloc_data() {
+                Some(block_id) => new_blocks.push(block_id),
+                None => {
+                    for block_id in new_blocks {
+                        fs.dealloc_data(block_id);
+                    }
+                    return false;
+                }
+            }
+        }
+        disk_inode.increase_size(new_size, new_blocks, &self.block_device);
+        true
+    }
+
+    /// 在当前目录下创建一个空文件，名字过长、同名文件已经存在或者空间不足时返回None
+    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        if name.len() > NAME_LENGTH_LIMIT {
+            return None;
+        }
+        let mut fs = self.fs.lock();
+        if self
+            .read_disk_inode(|root_inode| self.find_inode_id(name, root_inode))
+            .is_some()
+        {
+            return None;
+        }
+        let new_inode_id = fs.alloc_inode()?;
+        let (new_inode_block_id, new_inode_block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
+                new_inode.initialize(DiskInodeType::File);
+            });
+        // 在目录末尾追加一个目录项
+        let appended = self.modify_disk_inode(|root_inode| {
+            let file_count = root_inode.size as usize / DIRENT_SZ;
+            let new_size = (file_count + 1) * DIRENT_SZ;
+            if !self.increase_size(new_size as u32, root_inode, &mut fs) {
+                return false;
+            }
+            let dirent = DirEntry::new(name, new_inode_id).unwrap();
+            root_inode.write_at(file_count * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+            true
+        });
+        if !appended {
+            fs.dealloc_inode(new_inode_id);
+            return None;
+        }
+        let inode = self.inode_of(&fs, new_inode_id);
+        block_cache_sync_all();
+        Some(inode)
+    }
+
+    /// 删除当前目录下名为`name`的文件，回收它的索引节点和数据块，文件不存在时返回false
+    ///
+    /// 调用者需要保证没有其他`Inode`还在使用这个文件
+    pub fn remove(&self, name: &str) -> bool {
+        let mut fs = self.fs.lock();
+        let inode_id = match self.read_disk_inode(|root_inode| self.find_inode_id(name, root_inode)) {
+            Some(inode_id) => inode_id,
+            None => return false,
+        };
+        let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
+        let data_blocks = get_block_cache(block_id as usize, Arc::clone(&self.block_device))
+            .lock()
+            .modify(block_offset, |disk_inode: &mut DiskInode| {
+                disk_inode.clear_size(&self.block_device)
+            });
+        for data_block in data_blocks {
+            fs.dealloc_data(data_block);
+        }
+        fs.dealloc_inode(inode_id);
+        // 用剩下的目录项重写整个目录
+        self.modify_disk_inode(|root_inode| {
+            let file_count = root_inode.size as usize / DIRENT_SZ;
+            let mut dirents: Vec<DirEntry> = Vec::new();
+            for i in 0..file_count {
+                let mut dirent = DirEntry::empty();
+                assert_eq!(
+                    root_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device),
+                    DIRENT_SZ,
+                );
+                if dirent.name() != name {
+                    dirents.push(dirent);
+                }
+            }
+            for data_block in root_inode.clear_size(&self.block_device) {
+                fs.dealloc_data(data_block);
+            }
+            // 目录变小了，刚释放的数据块一定够用
+            assert!(self.increase_size((dirents.len() * DIRENT_SZ) as u32, root_inode, &mut fs));
+            for (i, dirent) in dirents.iter().enumerate() {
+                root_inode.write_at(i * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+            }
+        });
+        block_cache_sync_all();
+        true
+    }
+
+    /// 当前目录下所有文件的名字
+    pub fn ls(&self) -> Vec<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| {
+            let file_count = disk_inode.size as usize / DIRENT_SZ;
+            let mut v: Vec<String> = Vec::new();
+            for i in 0..file_count {
+                let mut dirent = DirEntry::empty();
+                assert_eq!(
+                    disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device),
+                    DIRENT_SZ,
+                );
+                v.push(String::from(dirent.name()));
+            }
+            v
+        })
+    }
+
+    /// 文件大小（字节）
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.size as usize)
+    }
+
+    /// 从`offset`开始读到`buf`中，返回读到的字节数
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
+    }
+
+    /// 从`offset`开始写入`buf`，文件不够大时自动扩大，返回写入的字节数，空间不足时返回0
+    pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut fs = self.fs.lock();
+        let size = self.modify_disk_inode(|disk_inode| {
+            if !self.increase_size((offset + buf.len()) as u32, disk_inode, &mut fs) {
+                return 0;
+            }
+            disk_inode.write_at(offset, buf, &self.block_device)
+        });
+        block_cache_sync_all();
+        size
+    }
+
+    /// 把文件大小清零，回收所有数据块
+    pub fn clear(&self) {
+        let mut fs = self.fs.lock();
+        self.modify_disk_inode(|disk_inode| {
+            let size = disk_inode.size;
+            let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
+            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
+            for data_block in data_blocks_dealloc.into_iter() {
+                fs.dealloc_data(data_block);
+            }
+        });
+        block_cache_sync_all();
+    }
+}
@@ -74,7 +74,17 @@ impl QEMUExit for RISCV64 {
     }
 }
 
-const VIRT_TEST: u64 = 0x100000;
+// 以下为QEMU virt的默认布局，设备树不可用时使用，正常情况下以设备树中的为准
+use crate::dtb::Region;
 
-pub const QEMU_EXIT_HANDLE: RISCV64 = RISCV64::new(VIRT_TEST);
-pub const CLOCK_FREQ: usize = 12500000;
\ No newline at end of file
+pub const MEMORY_START: usize = 0x80000000;
+pub const CLOCK_FREQ: usize = 12500000;
+pub const VIRT_TEST: Region = Region { base: 0x100000, size: 0x1000 };
+pub const RTC: Region = Region { base: 0x101000, size: 0x1000 };
+pub const UART: Region = Region { base: 0x10000000, size: 0x100 };
+pub const UART_IRQ: usize = 10;
+pub const PLIC: Region = Region { base: 0xc000000, size: 0x600000 };
+pub const VIRTIO0: usize = 0x10001000;
+pub const VIRTIO_SIZE: usize = 0x1000;
+
+pub const QEMU_EXIT_HANDLE: RISCV64 = RISCV64::new(VIRT_TEST.base as u64);
\ No newline at end of file
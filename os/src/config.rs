@@ -8,6 +8,12 @@ pub const KERNEL_STACK_SIZE: usize = 4096 * 2; // 8kb
 // 128KB
 // pub const APP_SIZE_LIMIT: usize = 0x20000;
 
+/// 所有应用统一链接的起始地址（见user/src/linker.ld）
+///
+/// 应用地址空间自下而上依次为：[0, USER_BASE)不映射，用于捕获空指针；ELF各段；
+/// 一个保护页；用户栈；向上增长的堆。mmap的区域由应用自己选择，最高到TRAP_CONTEXT为止
+pub const USER_BASE: usize = 0x10000;
+
 /// 跳板：4kb = 内存顶部 - 4kb
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 
@@ -15,21 +21,31 @@ pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 /// 操作系统会将当前的 CPU 寄存器状态和其他相关的上下文信息保存到 TRAP_CONTEXT 中，然后进入内核态（内核模式）处理异常或系统调用。
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
 
+/// 系统调用号的上限，用于统计每个系统调用的次数
+pub const MAX_SYSCALL_NUM: usize = 512;
+
+/// 每个任务最多同时打开的文件数，包括标准输入输出
+pub const MAX_FD: usize = 64;
+
 /// 内核堆大小 3145728 = 3mb
 pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
 
-/// qemu总内存限制在8mb
+/// 设备树不可用时使用的内存结束地址，qemu总内存限制在8mb
 pub const MEMORY_END: usize = 0x80800000;
 
 /// 1_0000_0000_0000 13位
 pub const PAGE_SIZE: usize = 4096;
 
-pub use crate::board::CLOCK_FREQ;
+/// pid的上限，同时存在的任务不能超过这个数量
+///
+/// 内核栈占据[TRAMPOLINE - MAX_PID * (KERNEL_STACK_SIZE + PAGE_SIZE), TRAMPOLINE)，
+/// 限制pid保证内核栈不会向下延伸到内核的其它映射
+pub const MAX_PID: usize = 1024;
 
 /// Return (bottom, top) of a kernel stack in kernel space.
-/// todo
-pub fn kernel_stack_position(app_id: usize) -> (usize, usize) {
-    let top = TRAMPOLINE - app_id * (KERNEL_STACK_SIZE + PAGE_SIZE);
+/// 每个内核栈之间隔着一个4kb的保护页，位置只由pid决定，pid回收后栈的位置也会被复用
+pub fn kernel_stack_position(pid: usize) -> (usize, usize) {
+    let top = TRAMPOLINE - pid * (KERNEL_STACK_SIZE + PAGE_SIZE);
     let bottom = top - KERNEL_STACK_SIZE;
     (bottom, top)
 }
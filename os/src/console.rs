@@ -1,19 +1,39 @@
-//! SBI console driver, for text output
+//! Console for text input and output
+//!
+//! 启动早期（设备树解析、开启分页之前）通过SBI输出；[`crate::drivers::init`]初始化UART之后
+//! 直接读写UART，SBI只在没有UART时使用。
 
-use crate::sbi::console_putchar;
+use crate::drivers::uart;
+use crate::sbi::{console_getchar, console_putchar};
 use core::fmt::{self, Write};
 
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
+        for c in s.bytes() {
+            putchar(c);
         }
         Ok(())
     }
 }
 
+/// 输出一个字节
+pub fn putchar(c: u8) {
+    match uart::console() {
+        Some(uart) => uart.putchar(c),
+        None => console_putchar(c as usize),
+    }
+}
+
+/// 读取一个字节，没有输入时返回None
+pub fn getchar() -> Option<u8> {
+    match uart::console() {
+        Some(uart) => uart.getchar(),
+        None => console_getchar(),
+    }
+}
+
 pub fn print(args: fmt::Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
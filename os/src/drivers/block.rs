@@ -0,0 +1,163 @@
+//! Block devices
+//!
+//! 文件系统通过[`BlockDevice`]读写以块为单位的存储设备，块大小为[`BLOCK_SIZE`]字节，
+//! 这两者都由[`easy_fs`]定义。
+//! 目前唯一的实现是virtio-blk，一次只处理一个请求，提交后轮询等待完成。
+
+use super::virtio::{DmaBuffer, VirtQueue, VirtioMmio, DEVICE_ID_BLOCK};
+use crate::dtb::machine_info;
+use crate::sync::UPSafeCell;
+use alloc::sync::Arc;
+use lazy_static::*;
+
+pub use easy_fs::{BlockDevice, BLOCK_SIZE};
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_S_OK: u8 = 0;
+
+const QUEUE_SIZE: u16 = 8;
+
+// 请求所用DMA页中各部分的偏移
+const HEADER_OFFSET: usize = 0;
+const STATUS_OFFSET: usize = 16;
+const DATA_OFFSET: usize = BLOCK_SIZE;
+
+/// 一次请求，读请求完成后数据被复制到缓冲区中
+enum Request<'a> {
+    Read(&'a mut [u8]),
+    Write(&'a [u8]),
+}
+
+/// 请求头
+#[repr(C)]
+struct BlockRequestHeader {
+    request_type: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+struct VirtioBlockInner {
+    transport: VirtioMmio,
+    queue: VirtQueue,
+    // 请求头、状态和数据都放在这一页中，数据在这里和调用者的缓冲区之间复制
+    request: DmaBuffer,
+}
+
+/// virtio-blk设备
+pub struct VirtioBlock {
+    inner: UPSafeCell<VirtioBlockInner>,
+    /// 容量（扇区数）
+    capacity: usize,
+}
+
+impl VirtioBlock {
+    /// 初始化设备，失败时返回None
+    pub fn new(transport: VirtioMmio) -> Option<Self> {
+        if transport.device_id() != DEVICE_ID_BLOCK || !transport.begin_init(0) {
+            return None;
+        }
+        let queue = VirtQueue::new(&transport, 0, QUEUE_SIZE)?;
+        transport.finish_init();
+        let capacity =
+            transport.read_config(0) as usize | (transport.read_config(4) as usize) << 32;
+        Some(Self {
+            inner: unsafe {
+                UPSafeCell::new(VirtioBlockInner {
+                    transport,
+                    queue,
+                    request: DmaBuffer::new(1),
+                })
+            },
+            capacity,
+        })
+    }
+
+    /// 容量（块数）
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// 提交一个请求并轮询等待完成
+    fn request(&self, block_id: usize, request: Request) {
+        assert!(block_id < self.capacity, "block {} out of range", block_id);
+        let mut inner = self.inner.exclusive_access();
+        let base = inner.request.vaddr();
+        let pa = inner.request.paddr();
+        let data =
+            unsafe { core::slice::from_raw_parts_mut((base + DATA_OFFSET) as *mut u8, BLOCK_SIZE) };
+        let (request_type, is_read) = match request {
+            Request::Read(_) => (VIRTIO_BLK_T_IN, true),
+            Request::Write(buf) => {
+                data.copy_from_slice(buf);
+                (VIRTIO_BLK_T_OUT, false)
+            }
+        };
+        unsafe {
+            ((base + HEADER_OFFSET) as *mut BlockRequestHeader).write_volatile(
+                BlockRequestHeader {
+                    request_type,
+                    reserved: 0,
+                    sector: block_id as u64,
+                },
+            );
+            ((base + STATUS_OFFSET) as *mut u8).write_volatile(0xff);
+        }
+        inner.queue.submit(&[
+            (
+                pa + HEADER_OFFSET,
+                core::mem::size_of::<BlockRequestHeader>(),
+                false,
+            ),
+            (pa + DATA_OFFSET, BLOCK_SIZE, is_read),
+            (pa + STATUS_OFFSET, 1, true),
+        ]);
+        inner.transport.notify(0);
+        while inner.queue.pop_used().is_none() {
+            core::hint::spin_loop();
+        }
+        inner.transport.ack_interrupt();
+        let status = unsafe { ((base + STATUS_OFFSET) as *const u8).read_volatile() };
+        assert_eq!(
+            status, VIRTIO_BLK_S_OK,
+            "virtio-blk request on block {} failed",
+            block_id
+        );
+        if let Request::Read(buf) = request {
+            buf.copy_from_slice(data);
+        }
+    }
+}
+
+impl BlockDevice for VirtioBlock {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        assert_eq!(buf.len(), BLOCK_SIZE);
+        self.request(block_id, Request::Read(buf));
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        assert_eq!(buf.len(), BLOCK_SIZE);
+        self.request(block_id, Request::Write(buf));
+    }
+}
+
+lazy_static! {
+    /// 第一个virtio-blk设备，没有时为None
+    pub static ref BLOCK_DEVICE: Option<Arc<dyn BlockDevice>> = machine_info()
+        .virtio()
+        .iter()
+        .filter_map(|&region| VirtioMmio::probe(region))
+        .find(|transport| transport.device_id() == DEVICE_ID_BLOCK)
+        .and_then(VirtioBlock::new)
+        .map(|device| {
+            println!("[kernel] virtio-blk: {} blocks", device.capacity());
+            Arc::new(device) as Arc<dyn BlockDevice>
+        });
+}
+
+/// 探测块设备
+pub fn init() {
+    if BLOCK_DEVICE.is_none() {
+        println!("[kernel] no virtio-blk device");
+    }
+}
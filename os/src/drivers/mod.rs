@@ -0,0 +1,22 @@
+//! Device drivers
+//!
+//! 设备的MMIO区域由[`crate::dtb`]从设备树中找到，并在[`KERNEL_SPACE`](crate::mm::KERNEL_SPACE)中
+//! 恒等映射，所以驱动直接用物理地址访问寄存器。设备树中没有的设备对应的驱动不会被创建。
+//!
+//! 需要中断的驱动通过[`plic::register_irq`]注册处理函数，S态外部中断到来时由
+//! [`plic::handle_external_interrupt`]按中断号分发。
+
+pub mod block;
+pub mod plic;
+pub mod rtc;
+pub mod uart;
+pub mod virtio;
+
+/// 初始化所有设备，需要在开启分页之后调用
+pub fn init() {
+    plic::init();
+    // 之后的输出都直接写UART
+    uart::init();
+    rtc::init();
+    block::init();
+}
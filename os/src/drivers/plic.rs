@@ -0,0 +1,124 @@
+//! Platform-Level Interrupt Controller
+//!
+//! 外部设备的中断都经过PLIC：设备中断号的优先级高于当前上下文的阈值、并且在该上下文中被使能时，
+//! PLIC向hart发出外部中断。内核通过claim取得中断号，处理完之后complete。
+//!
+//! 规范：<https://github.com/riscv/riscv-plic-spec/blob/master/riscv-plic.adoc>
+
+use crate::dtb::machine_info;
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use lazy_static::*;
+
+const PRIORITY_BASE: usize = 0x0;
+const ENABLE_BASE: usize = 0x2000;
+const ENABLE_STRIDE: usize = 0x80;
+const CONTEXT_BASE: usize = 0x20_0000;
+const CONTEXT_STRIDE: usize = 0x1000;
+// 每个上下文中阈值和claim/complete寄存器的偏移
+const THRESHOLD: usize = 0x0;
+const CLAIM_COMPLETE: usize = 0x4;
+
+/// hart 0的S态上下文，QEMU virt上每个hart依次有M态和S态两个上下文
+const SUPERVISOR_CONTEXT: usize = 1;
+/// 注册的中断默认使用的优先级，0表示永远不会触发
+const DEFAULT_PRIORITY: u32 = 1;
+
+/// PLIC设备
+pub struct Plic {
+    base: usize,
+}
+
+impl Plic {
+    pub fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    fn reg(&self, offset: usize) -> *mut u32 {
+        (self.base + offset) as *mut u32
+    }
+
+    /// 设置中断源`irq`的优先级
+    pub fn set_priority(&self, irq: usize, priority: u32) {
+        unsafe { self.reg(PRIORITY_BASE + irq * 4).write_volatile(priority) }
+    }
+
+    /// 优先级不超过`threshold`的中断不会发给`context`
+    pub fn set_threshold(&self, context: usize, threshold: u32) {
+        unsafe {
+            self.reg(CONTEXT_BASE + context * CONTEXT_STRIDE + THRESHOLD)
+                .write_volatile(threshold)
+        }
+    }
+
+    /// 在`context`中使能中断源`irq`
+    pub fn enable(&self, context: usize, irq: usize) {
+        let reg = self.reg(ENABLE_BASE + context * ENABLE_STRIDE + irq / 32 * 4);
+        unsafe { reg.write_volatile(reg.read_volatile() | 1 << (irq % 32)) }
+    }
+
+    /// 取得`context`中优先级最高的待处理中断，没有时返回None
+    pub fn claim(&self, context: usize) -> Option<usize> {
+        let irq = unsafe {
+            self.reg(CONTEXT_BASE + context * CONTEXT_STRIDE + CLAIM_COMPLETE)
+                .read_volatile()
+        };
+        if irq == 0 {
+            None
+        } else {
+            Some(irq as usize)
+        }
+    }
+
+    /// 通知PLIC中断`irq`已经处理完，之后它才能再次触发
+    pub fn complete(&self, context: usize, irq: usize) {
+        unsafe {
+            self.reg(CONTEXT_BASE + context * CONTEXT_STRIDE + CLAIM_COMPLETE)
+                .write_volatile(irq as u32)
+        }
+    }
+}
+
+lazy_static! {
+    /// 设备树中没有PLIC时为None
+    pub static ref PLIC: Option<Plic> = machine_info().plic.map(|region| Plic::new(region.base));
+    /// 中断号 -> 处理函数
+    static ref IRQ_HANDLERS: UPSafeCell<BTreeMap<usize, fn()>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// S态上下文接收所有优先级大于0的中断
+pub fn init() {
+    if let Some(plic) = PLIC.as_ref() {
+        plic.set_threshold(SUPERVISOR_CONTEXT, 0);
+    }
+}
+
+/// 中断`irq`到来时调用`handler`，返回false表示没有PLIC
+pub fn register_irq(irq: usize, handler: fn()) -> bool {
+    match PLIC.as_ref() {
+        Some(plic) => {
+            IRQ_HANDLERS.exclusive_access().insert(irq, handler);
+            plic.set_priority(irq, DEFAULT_PRIORITY);
+            plic.enable(SUPERVISOR_CONTEXT, irq);
+            true
+        }
+        None => false,
+    }
+}
+
+/// 处理所有待处理的外部中断，由S态外部中断调用
+pub fn handle_external_interrupt() {
+    let plic = PLIC.as_ref().expect("external interrupt without a PLIC");
+    while let Some(irq) = plic.claim(SUPERVISOR_CONTEXT) {
+        // 处理函数可能会注册新的中断，先释放IRQ_HANDLERS
+        let handler = IRQ_HANDLERS.exclusive_access().get(&irq).copied();
+        match handler {
+            Some(handler) => handler(),
+            None => {
+                println!("[kernel] unexpected external interrupt {}", irq);
+            }
+        }
+        plic.complete(SUPERVISOR_CONTEXT, irq);
+    }
+}
@@ -0,0 +1,106 @@
+//! Google Goldfish RTC
+//!
+//! QEMU `virt`上的实时时钟，两个32位寄存器合起来是Unix时间（纳秒）。
+//!
+//! 参考：<https://android.googlesource.com/platform/external/qemu/+/master/docs/GOLDFISH-VIRTUAL-HARDWARE.TXT>
+
+use crate::dtb::machine_info;
+use core::fmt::{self, Display, Formatter};
+use lazy_static::*;
+
+const TIME_LOW: usize = 0x00;
+const TIME_HIGH: usize = 0x04;
+
+const NANO_PER_SEC: usize = 1_000_000_000;
+
+/// Goldfish RTC设备
+pub struct GoldfishRtc {
+    base: usize,
+}
+
+impl GoldfishRtc {
+    pub fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    /// 当前的Unix时间（纳秒）
+    pub fn read_time_ns(&self) -> usize {
+        unsafe {
+            // 读低32位时设备会锁存高32位，所以必须先读低位
+            let low = ((self.base + TIME_LOW) as *const u32).read_volatile();
+            let high = ((self.base + TIME_HIGH) as *const u32).read_volatile();
+            ((high as usize) << 32) | low as usize
+        }
+    }
+}
+
+lazy_static! {
+    /// 设备树中没有RTC时为None
+    pub static ref RTC: Option<GoldfishRtc> = machine_info().rtc.map(|region| GoldfishRtc::new(region.base));
+}
+
+/// 从RTC读出的Unix时间（纳秒），没有RTC时返回None
+pub fn unix_time_ns() -> Option<usize> {
+    RTC.as_ref().map(|rtc| rtc.read_time_ns())
+}
+
+/// 打印启动时刻的日期
+pub fn init() {
+    match unix_time_ns() {
+        Some(ns) => {
+            println!("[kernel] rtc: {}", DateTime::from_unix_secs(ns / NANO_PER_SEC));
+        }
+        None => {
+            println!("[kernel] no rtc, realtime starts from boot");
+        }
+    }
+}
+
+/// UTC日期和时间，用于打印日志
+#[derive(Copy, Clone, Debug)]
+pub struct DateTime {
+    pub year: usize,
+    pub month: usize,
+    pub day: usize,
+    pub hour: usize,
+    pub minute: usize,
+    pub second: usize,
+}
+
+impl DateTime {
+    /// 1970-01-01以来的秒数转换为日期
+    ///
+    /// 算法参考：<http://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+    pub fn from_unix_secs(secs: usize) -> Self {
+        let days = secs / 86400;
+        let rest = secs % 86400;
+        // 把纪元移到0000-03-01，闰日落在每年的最后
+        let z = days + 719468;
+        let era = z / 146097;
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as usize;
+        Self {
+            year,
+            month,
+            day,
+            hour: rest / 3600,
+            minute: rest % 3600 / 60,
+            second: rest % 60,
+        }
+    }
+}
+
+impl Display for DateTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
@@ -0,0 +1,118 @@
+//! NS16550A UART
+//!
+//! 发送时直接轮询LSR写THR，不再经过SBI；接收到的字符先放进环形缓冲区，
+//! 外部中断到来时（或者读取时）从RX FIFO中取出。
+//!
+//! 寄存器说明：<http://caro.su/msx/ocm_de1/16550.pdf>
+
+use super::plic::register_irq;
+use crate::dtb::machine_info;
+use crate::sync::UPSafeCell;
+use alloc::collections::VecDeque;
+use core::sync::atomic::{AtomicBool, Ordering};
+use lazy_static::*;
+
+// 寄存器偏移，每个寄存器占一个字节
+const RBR: usize = 0; // 读：接收缓冲
+const THR: usize = 0; // 写：发送保持
+const IER: usize = 1; // 中断使能
+const FCR: usize = 2; // 写：FIFO控制
+const LCR: usize = 3; // 线路控制
+const MCR: usize = 4; // modem控制
+const LSR: usize = 5; // 线路状态
+
+const IER_RX_AVAILABLE: u8 = 1 << 0;
+const FCR_FIFO_ENABLE: u8 = 1 << 0;
+const FCR_CLEAR: u8 = 0b11 << 1;
+const LCR_8N1: u8 = 0b11;
+// OUT2连接着中断输出，不打开时部分实现不会发出中断
+const MCR_DTR_RTS_OUT2: u8 = 0b1011;
+const LSR_DATA_READY: u8 = 1 << 0;
+const LSR_THR_EMPTY: u8 = 1 << 5;
+
+/// 接收缓冲区的容量，满了之后新到的字符被丢弃
+const RX_BUFFER_SIZE: usize = 256;
+
+/// NS16550A设备
+pub struct Ns16550 {
+    base: usize,
+    rx_buffer: UPSafeCell<VecDeque<u8>>,
+}
+
+impl Ns16550 {
+    pub fn new(base: usize) -> Self {
+        Self {
+            base,
+            rx_buffer: unsafe { UPSafeCell::new(VecDeque::with_capacity(RX_BUFFER_SIZE)) },
+        }
+    }
+
+    fn read_reg(&self, reg: usize) -> u8 {
+        unsafe { ((self.base + reg) as *const u8).read_volatile() }
+    }
+
+    fn write_reg(&self, reg: usize, value: u8) {
+        unsafe { ((self.base + reg) as *mut u8).write_volatile(value) }
+    }
+
+    /// 8位数据、无校验、1位停止位，打开FIFO和接收中断；波特率沿用固件的设置
+    fn init(&self) {
+        self.write_reg(IER, 0);
+        self.write_reg(LCR, LCR_8N1);
+        self.write_reg(FCR, FCR_FIFO_ENABLE | FCR_CLEAR);
+        self.write_reg(MCR, MCR_DTR_RTS_OUT2);
+        self.write_reg(IER, IER_RX_AVAILABLE);
+    }
+
+    /// 等待发送保持寄存器为空后发送一个字节
+    pub fn putchar(&self, c: u8) {
+        while self.read_reg(LSR) & LSR_THR_EMPTY == 0 {}
+        self.write_reg(THR, c);
+    }
+
+    /// 把RX FIFO中的字符全部移到接收缓冲区，由外部中断调用
+    pub fn handle_irq(&self) {
+        let mut rx_buffer = self.rx_buffer.exclusive_access();
+        while self.read_reg(LSR) & LSR_DATA_READY != 0 {
+            let c = self.read_reg(RBR);
+            if rx_buffer.len() < RX_BUFFER_SIZE {
+                rx_buffer.push_back(c);
+            }
+        }
+    }
+
+    /// 取出一个已经收到的字符，没有输入时返回None
+    pub fn getchar(&self) -> Option<u8> {
+        // 中断可能还没来得及处理，先检查一次FIFO
+        self.handle_irq();
+        self.rx_buffer.exclusive_access().pop_front()
+    }
+}
+
+lazy_static! {
+    /// 设备树中没有UART时为None
+    pub static ref UART: Option<Ns16550> = machine_info().uart.map(|region| Ns16550::new(region.base));
+}
+
+// print可能在任何地方被调用（包括panic时），所以用原子变量而不是UPSafeCell记录状态
+static CONSOLE_READY: AtomicBool = AtomicBool::new(false);
+
+/// 初始化UART并注册接收中断，之后控制台改为直接使用它
+pub fn init() {
+    if let Some(uart) = UART.as_ref() {
+        uart.init();
+        if let Some(irq) = machine_info().uart_irq {
+            register_irq(irq, || UART.as_ref().unwrap().handle_irq());
+        }
+        CONSOLE_READY.store(true, Ordering::Release);
+    }
+}
+
+/// 已经初始化、可以作为控制台的UART
+pub fn console() -> Option<&'static Ns16550> {
+    if CONSOLE_READY.load(Ordering::Acquire) {
+        UART.as_ref()
+    } else {
+        None
+    }
+}
@@ -0,0 +1,325 @@
+//! virtio-mmio transport
+//!
+//! 同时支持legacy（version 1，QEMU默认）和modern（version 2）两种接口。virtqueue的内存从
+//! 物理页帧分配器中连续分配，设备通过物理地址访问它们；内核地址空间恒等映射了全部物理内存，
+//! 所以内核可以用同一个地址读写。
+//!
+//! 规范：<https://docs.oasis-open.org/virtio/virtio/v1.1/virtio-v1.1.html>
+
+use crate::config::PAGE_SIZE;
+use crate::dtb::Region;
+use crate::mm::{frame_alloc_contiguous, FrameTracker, PhysAddr, VirtAddr, KERNEL_SPACE};
+use alloc::vec::Vec;
+use core::sync::atomic::{fence, Ordering};
+
+// 寄存器偏移
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES: usize = 0x010;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+const GUEST_PAGE_SIZE: usize = 0x028; // legacy
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+const QUEUE_ALIGN: usize = 0x03c; // legacy
+const QUEUE_PFN: usize = 0x040; // legacy
+const QUEUE_READY: usize = 0x044; // modern
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080; // modern，以下同
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+/// 设备相关的配置空间
+const CONFIG: usize = 0x100;
+
+const MAGIC: u32 = 0x7472_6976; // "virt"
+
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+
+/// modern设备必须协商的特性
+const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+/// 设备类型
+pub const DEVICE_ID_BLOCK: u32 = 2;
+
+/// 一个virtio-mmio设备的寄存器
+pub struct VirtioMmio {
+    base: usize,
+    version: u32,
+}
+
+impl VirtioMmio {
+    /// 检查`region`处是否有设备，空的插槽device id为0
+    pub fn probe(region: Region) -> Option<Self> {
+        let transport = Self {
+            base: region.base,
+            version: 0,
+        };
+        let version = transport.read(VERSION);
+        if transport.read(MAGIC_VALUE) != MAGIC
+            || !(1..=2).contains(&version)
+            || transport.device_id() == 0
+        {
+            return None;
+        }
+        Some(Self {
+            version,
+            ..transport
+        })
+    }
+
+    fn read(&self, offset: usize) -> u32 {
+        unsafe { ((self.base + offset) as *const u32).read_volatile() }
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        unsafe { ((self.base + offset) as *mut u32).write_volatile(value) }
+    }
+
+    pub fn device_id(&self) -> u32 {
+        self.read(DEVICE_ID)
+    }
+
+    fn is_legacy(&self) -> bool {
+        self.version == 1
+    }
+
+    /// 读取配置空间中偏移为`offset`的32位值
+    pub fn read_config(&self, offset: usize) -> u32 {
+        self.read(CONFIG + offset)
+    }
+
+    /// 复位设备并协商特性，`supported`之外的特性都不接受；返回false表示设备不接受协商结果
+    pub fn begin_init(&self, supported: u64) -> bool {
+        self.write(STATUS, 0);
+        self.write(STATUS, STATUS_ACKNOWLEDGE);
+        self.write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        self.write(DEVICE_FEATURES_SEL, 0);
+        let mut features = self.read(DEVICE_FEATURES) as u64;
+        self.write(DEVICE_FEATURES_SEL, 1);
+        features |= (self.read(DEVICE_FEATURES) as u64) << 32;
+
+        let mut accepted = features & supported;
+        if !self.is_legacy() {
+            accepted |= features & VIRTIO_F_VERSION_1;
+        }
+        self.write(DRIVER_FEATURES_SEL, 0);
+        self.write(DRIVER_FEATURES, accepted as u32);
+        self.write(DRIVER_FEATURES_SEL, 1);
+        self.write(DRIVER_FEATURES, (accepted >> 32) as u32);
+
+        if self.is_legacy() {
+            // legacy设备没有FEATURES_OK，用QueuePFN给出队列的页号
+            self.write(GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+            return true;
+        }
+        let status = STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_FEATURES_OK;
+        self.write(STATUS, status);
+        self.read(STATUS) & STATUS_FEATURES_OK != 0
+    }
+
+    /// 队列设置完成，设备开始工作
+    pub fn finish_init(&self) {
+        let status = self.read(STATUS);
+        self.write(STATUS, status | STATUS_DRIVER_OK);
+    }
+
+    /// 把第`index`个队列设置为`queue`
+    fn setup_queue(&self, index: u32, queue: &VirtQueue) -> bool {
+        self.write(QUEUE_SEL, index);
+        let max = self.read(QUEUE_NUM_MAX);
+        let in_use = if self.is_legacy() {
+            self.read(QUEUE_PFN) != 0
+        } else {
+            self.read(QUEUE_READY) != 0
+        };
+        if max < queue.size as u32 || in_use {
+            return false;
+        }
+        self.write(QUEUE_NUM, queue.size as u32);
+        if self.is_legacy() {
+            self.write(QUEUE_ALIGN, PAGE_SIZE as u32);
+            self.write(QUEUE_PFN, (queue.desc_pa() / PAGE_SIZE) as u32);
+        } else {
+            let write_addr = |low, high, pa: usize| {
+                self.write(low, pa as u32);
+                self.write(high, (pa >> 32) as u32);
+            };
+            write_addr(QUEUE_DESC_LOW, QUEUE_DESC_HIGH, queue.desc_pa());
+            write_addr(QUEUE_DRIVER_LOW, QUEUE_DRIVER_HIGH, queue.avail_pa);
+            write_addr(QUEUE_DEVICE_LOW, QUEUE_DEVICE_HIGH, queue.used_pa);
+            self.write(QUEUE_READY, 1);
+        }
+        true
+    }
+
+    /// 通知设备第`index`个队列有新的请求
+    pub fn notify(&self, index: u32) {
+        self.write(QUEUE_NOTIFY, index);
+    }
+
+    /// 应答设备发出的中断
+    pub fn ack_interrupt(&self) {
+        let status = self.read(INTERRUPT_STATUS);
+        self.write(INTERRUPT_ACK, status);
+    }
+}
+
+/// 从物理页帧分配器中分配的DMA内存
+pub struct DmaBuffer {
+    frames: Vec<FrameTracker>,
+}
+
+impl DmaBuffer {
+    /// 分配`pages`个物理上连续、已经清零的页面
+    pub fn new(pages: usize) -> Self {
+        let frames = frame_alloc_contiguous(pages, 1).expect("out of memory for DMA");
+        let buffer = Self { frames };
+        // 设备使用物理地址，内核使用虚拟地址，两者必须指向同一块内存
+        let va = VirtAddr::from(buffer.vaddr());
+        let pte = KERNEL_SPACE
+            .exclusive_access()
+            .translate(va.floor())
+            .unwrap();
+        assert!(
+            pte.ppn() == buffer.frames[0].ppn,
+            "DMA buffer is not identity mapped"
+        );
+        buffer
+    }
+
+    /// 设备看到的物理地址
+    pub fn paddr(&self) -> usize {
+        PhysAddr::from(self.frames[0].ppn).0
+    }
+
+    /// 内核中访问它的地址，物理内存是恒等映射的
+    pub fn vaddr(&self) -> usize {
+        self.paddr()
+    }
+}
+
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+
+/// 描述符表中的一项
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+/// used ring中的一项
+#[repr(C)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+/// 一个virtqueue
+///
+/// 内存布局和legacy接口的要求一致：第一页依次是描述符表和available ring，used ring在第二页开头。
+/// 驱动每次只提交一个请求并等待它完成，所以每个请求总是从第0个描述符开始
+pub struct VirtQueue {
+    buffer: DmaBuffer,
+    size: u16,
+    avail_pa: usize,
+    used_pa: usize,
+    avail_idx: u16,
+    last_used_idx: u16,
+}
+
+impl VirtQueue {
+    /// 创建一个`size`项的队列，交给`transport`的第`index`个队列
+    pub fn new(transport: &VirtioMmio, index: u32, size: u16) -> Option<Self> {
+        let size_usize = size as usize;
+        assert!(size_usize * 16 + 6 + size_usize * 2 <= PAGE_SIZE);
+        let buffer = DmaBuffer::new(2);
+        let desc_pa = buffer.paddr();
+        let queue = Self {
+            size,
+            avail_pa: desc_pa + size_usize * core::mem::size_of::<Descriptor>(),
+            used_pa: desc_pa + PAGE_SIZE,
+            avail_idx: 0,
+            last_used_idx: 0,
+            buffer,
+        };
+        if transport.setup_queue(index, &queue) {
+            Some(queue)
+        } else {
+            None
+        }
+    }
+
+    fn desc_pa(&self) -> usize {
+        self.buffer.paddr()
+    }
+
+    /// 物理内存恒等映射，物理地址可以直接当作指针使用
+    fn desc(&self, i: usize) -> *mut Descriptor {
+        (self.desc_pa() as *mut Descriptor).wrapping_add(i)
+    }
+
+    /// 提交一个由若干段缓冲区组成的请求，每段是(物理地址, 长度, 设备是否写入)
+    pub fn submit(&mut self, buffers: &[(usize, usize, bool)]) {
+        assert!(!buffers.is_empty() && buffers.len() <= self.size as usize);
+        for (i, &(addr, len, device_writes)) in buffers.iter().enumerate() {
+            let mut flags = if device_writes { DESC_F_WRITE } else { 0 };
+            if i + 1 < buffers.len() {
+                flags |= DESC_F_NEXT;
+            }
+            unsafe {
+                self.desc(i).write_volatile(Descriptor {
+                    addr: addr as u64,
+                    len: len as u32,
+                    flags,
+                    next: (i + 1) as u16,
+                });
+            }
+        }
+        // avail ring: flags(u16) idx(u16) ring[size](u16)
+        let ring = (self.avail_pa + 4) as *mut u16;
+        let idx = (self.avail_pa + 2) as *mut u16;
+        unsafe {
+            ring.add((self.avail_idx % self.size) as usize)
+                .write_volatile(0);
+            // 设备必须先看到描述符，再看到新的idx
+            fence(Ordering::SeqCst);
+            self.avail_idx = self.avail_idx.wrapping_add(1);
+            idx.write_volatile(self.avail_idx);
+        }
+        fence(Ordering::SeqCst);
+    }
+
+    /// 取出一个已经完成的请求，返回设备写入的字节数
+    pub fn pop_used(&mut self) -> Option<u32> {
+        // used ring: flags(u16) idx(u16) ring[size](UsedElem)
+        let idx = unsafe { ((self.used_pa + 2) as *const u16).read_volatile() };
+        if idx == self.last_used_idx {
+            return None;
+        }
+        fence(Ordering::SeqCst);
+        let ring = (self.used_pa + 4) as *const UsedElem;
+        let elem = unsafe {
+            ring.add((self.last_used_idx % self.size) as usize)
+                .read_volatile()
+        };
+        self.last_used_idx = self.last_used_idx.wrapping_add(1);
+        debug_assert_eq!(elem.id, 0);
+        Some(elem.len)
+    }
+}
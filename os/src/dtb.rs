@@ -0,0 +1,336 @@
+//! Flattened device tree parsing
+//!
+//! SBI跳转到内核时在a1中传入设备树（DTB）的物理地址，这里在开启分页之前把需要的信息取出来：
+//! 内存范围、时钟频率，以及UART、PLIC、RTC、virtio-mmio等设备的MMIO区域。
+//! 解析过程不申请堆内存，结果保存在[`MachineInfo`]中，之后DTB所在的物理页可以被正常分配。
+//!
+//! 格式参考：<https://devicetree-specification.readthedocs.io/en/stable/flattened-format.html>
+
+use crate::board;
+use crate::config::MEMORY_END;
+use crate::sync::UPSafeCell;
+use lazy_static::*;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// 节点的最大嵌套深度
+const MAX_DEPTH: usize = 16;
+/// 最多记录的virtio-mmio设备数量，QEMU virt默认提供8个
+pub const MAX_VIRTIO: usize = 8;
+
+/// 一段物理地址区间[base, base + size)
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Region {
+    pub base: usize,
+    pub size: usize,
+}
+
+impl Region {
+    pub fn end(&self) -> usize {
+        self.base + self.size
+    }
+}
+
+/// 从设备树中得到的硬件信息
+#[derive(Copy, Clone, Debug)]
+pub struct MachineInfo {
+    /// 内核所在的那一段内存
+    pub memory: Region,
+    /// mtime计数器的频率
+    pub timebase_frequency: usize,
+    pub uart: Option<Region>,
+    /// UART在PLIC上的中断号
+    pub uart_irq: Option<usize>,
+    pub plic: Option<Region>,
+    /// 用于退出QEMU的sifive_test设备
+    pub test: Option<Region>,
+    /// Goldfish实时时钟
+    pub rtc: Option<Region>,
+    virtio: [Region; MAX_VIRTIO],
+    virtio_count: usize,
+}
+
+impl MachineInfo {
+    /// 没有可用的设备树时，使用QEMU virt的默认布局
+    fn qemu_default() -> Self {
+        let mut virtio = [Region::default(); MAX_VIRTIO];
+        for (i, slot) in virtio.iter_mut().enumerate() {
+            *slot = Region {
+                base: board::VIRTIO0 + i * board::VIRTIO_SIZE,
+                size: board::VIRTIO_SIZE,
+            };
+        }
+        Self {
+            memory: Region {
+                base: board::MEMORY_START,
+                size: MEMORY_END - board::MEMORY_START,
+            },
+            timebase_frequency: board::CLOCK_FREQ,
+            uart: Some(board::UART),
+            uart_irq: Some(board::UART_IRQ),
+            plic: Some(board::PLIC),
+            test: Some(board::VIRT_TEST),
+            rtc: Some(board::RTC),
+            virtio,
+            virtio_count: MAX_VIRTIO,
+        }
+    }
+
+    fn empty() -> Self {
+        Self {
+            memory: Region::default(),
+            timebase_frequency: 0,
+            uart: None,
+            uart_irq: None,
+            plic: None,
+            test: None,
+            rtc: None,
+            virtio: [Region::default(); MAX_VIRTIO],
+            virtio_count: 0,
+        }
+    }
+
+    /// 所有virtio-mmio设备的区域，按在设备树中出现的顺序排列
+    pub fn virtio(&self) -> &[Region] {
+        &self.virtio[..self.virtio_count]
+    }
+
+    /// 需要在内核地址空间中恒等映射的所有MMIO区域
+    pub fn mmio_regions(&self) -> impl Iterator<Item = Region> + '_ {
+        self.uart
+            .iter()
+            .chain(self.plic.iter())
+            .chain(self.test.iter())
+            .chain(self.rtc.iter())
+            .chain(self.virtio().iter())
+            .copied()
+    }
+}
+
+lazy_static! {
+    static ref MACHINE_INFO: UPSafeCell<MachineInfo> =
+        unsafe { UPSafeCell::new(MachineInfo::qemu_default()) };
+}
+
+/// 解析`dtb_pa`处的设备树，必须在开启分页之前调用；解析失败时保留QEMU virt的默认值
+pub fn init(dtb_pa: usize) {
+    match unsafe { parse(dtb_pa) } {
+        Some(info) => {
+            println!(
+                "[kernel] dtb: memory [{:#x}, {:#x}), timebase {} Hz, {} virtio slot(s)",
+                info.memory.base,
+                info.memory.end(),
+                info.timebase_frequency,
+                info.virtio_count
+            );
+            *MACHINE_INFO.exclusive_access() = info;
+        }
+        None => {
+            println!("[kernel] no valid dtb at {:#x}, using defaults", dtb_pa);
+        }
+    }
+}
+
+/// 当前机器的硬件信息
+pub fn machine_info() -> MachineInfo {
+    *MACHINE_INFO.exclusive_access()
+}
+
+/// mtime计数器的频率
+pub fn clock_freq() -> usize {
+    MACHINE_INFO.exclusive_access().timebase_frequency
+}
+
+/// 物理内存的结束地址
+pub fn memory_end() -> usize {
+    MACHINE_INFO.exclusive_access().memory.end()
+}
+
+/// 设备树中的整数都是大端序
+unsafe fn read_be32(addr: usize) -> u32 {
+    u32::from_be((addr as *const u32).read_volatile())
+}
+
+/// 读取以`addr`开头、以0结尾的字符串
+unsafe fn read_cstr(addr: usize) -> &'static [u8] {
+    let mut len = 0;
+    while *((addr + len) as *const u8) != 0 {
+        len += 1;
+    }
+    core::slice::from_raw_parts(addr as *const u8, len)
+}
+
+/// 读取由`cells`个32位整数拼成的数
+unsafe fn read_cells(addr: usize, cells: usize) -> usize {
+    (0..cells).fold(0, |value, i| (value << 32) | read_be32(addr + i * 4) as usize)
+}
+
+fn align4(x: usize) -> usize {
+    (x + 3) & !3
+}
+
+/// 设备的种类，由compatible属性决定
+#[derive(Copy, Clone, PartialEq)]
+enum Device {
+    Unknown,
+    Uart,
+    Plic,
+    Test,
+    Rtc,
+    Virtio,
+}
+
+fn device_from_compatible(compatible: &[u8]) -> Device {
+    // compatible是若干个以0分隔的字符串
+    for name in compatible.split(|&b| b == 0) {
+        match name {
+            b"ns16550a" => return Device::Uart,
+            b"riscv,plic0" | b"sifive,plic-1.0.0" => return Device::Plic,
+            b"sifive,test0" => return Device::Test,
+            b"google,goldfish-rtc" => return Device::Rtc,
+            b"virtio,mmio" => return Device::Virtio,
+            _ => {}
+        }
+    }
+    Device::Unknown
+}
+
+/// 正在解析的节点的状态
+#[derive(Copy, Clone)]
+struct Node {
+    // 子节点reg属性中地址和长度分别占几个32位整数
+    address_cells: usize,
+    size_cells: usize,
+    is_memory: bool,
+    device: Device,
+    reg: Option<Region>,
+    // interrupts属性的第一个中断号
+    irq: Option<usize>,
+}
+
+impl Node {
+    fn new() -> Self {
+        // 规范中的默认值
+        Self {
+            address_cells: 2,
+            size_cells: 1,
+            is_memory: false,
+            device: Device::Unknown,
+            reg: None,
+            irq: None,
+        }
+    }
+}
+
+/// 遍历结构块，找出需要的节点
+unsafe fn parse(dtb_pa: usize) -> Option<MachineInfo> {
+    if dtb_pa == 0 || dtb_pa % 4 != 0 || read_be32(dtb_pa) != FDT_MAGIC {
+        return None;
+    }
+    let struct_base = dtb_pa + read_be32(dtb_pa + 8) as usize;
+    let strings_base = dtb_pa + read_be32(dtb_pa + 12) as usize;
+
+    extern "C" {
+        fn skernel();
+    }
+    let kernel_start = skernel as usize;
+
+    let mut info = MachineInfo::empty();
+    let mut stack = [Node::new(); MAX_DEPTH];
+    // depth为当前节点在stack中的下标，根节点为1，stack[0]只提供根节点reg的默认格式
+    let mut depth = 0;
+    let mut pos = struct_base;
+    loop {
+        let token = read_be32(pos);
+        pos += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = read_cstr(pos);
+                pos = align4(pos + name.len() + 1);
+                depth += 1;
+                if depth >= MAX_DEPTH {
+                    return None;
+                }
+                stack[depth] = Node {
+                    is_memory: name == b"memory" || name.starts_with(b"memory@"),
+                    ..Node::new()
+                };
+            }
+            FDT_END_NODE => {
+                if depth == 0 {
+                    return None;
+                }
+                let node = stack[depth];
+                if let Some(reg) = node.reg {
+                    match node.device {
+                        Device::Uart => {
+                            info.uart = Some(reg);
+                            info.uart_irq = node.irq;
+                        }
+                        Device::Plic => info.plic = Some(reg),
+                        Device::Test => info.test = Some(reg),
+                        Device::Rtc => info.rtc = Some(reg),
+                        Device::Virtio if info.virtio_count < MAX_VIRTIO => {
+                            info.virtio[info.virtio_count] = reg;
+                            info.virtio_count += 1;
+                        }
+                        _ => {}
+                    }
+                    // 可能有多段内存，选内核所在的那一段
+                    if node.is_memory && reg.base <= kernel_start && kernel_start < reg.end() {
+                        info.memory = reg;
+                    }
+                }
+                depth -= 1;
+            }
+            FDT_PROP => {
+                let len = read_be32(pos) as usize;
+                let name = read_cstr(strings_base + read_be32(pos + 4) as usize);
+                let value = pos + 8;
+                pos = align4(value + len);
+                if depth == 0 {
+                    return None;
+                }
+                let parent = stack[depth - 1];
+                let node = &mut stack[depth];
+                match name {
+                    b"#address-cells" => node.address_cells = read_be32(value) as usize,
+                    b"#size-cells" => node.size_cells = read_be32(value) as usize,
+                    b"device_type" => {
+                        node.is_memory |= read_cstr(value) == b"memory";
+                    }
+                    b"compatible" => {
+                        let compatible = core::slice::from_raw_parts(value as *const u8, len);
+                        node.device = device_from_compatible(compatible);
+                    }
+                    // 只关心第一段
+                    b"reg" if len >= (parent.address_cells + parent.size_cells) * 4 => {
+                        node.reg = Some(Region {
+                            base: read_cells(value, parent.address_cells),
+                            size: read_cells(value + parent.address_cells * 4, parent.size_cells),
+                        });
+                    }
+                    b"interrupts" if len >= 4 => node.irq = Some(read_be32(value) as usize),
+                    // 通常在/cpus节点中，也可能出现在每个cpu节点中
+                    b"timebase-frequency" => {
+                        info.timebase_frequency = read_cells(value, len / 4);
+                    }
+                    _ => {}
+                }
+            }
+            FDT_NOP => {}
+            FDT_END => break,
+            _ => return None,
+        }
+    }
+
+    if info.memory.size == 0 || info.timebase_frequency == 0 {
+        return None;
+    }
+    Some(info)
+}
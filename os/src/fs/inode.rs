@@ -0,0 +1,183 @@
+//! 块设备上的easy-fs
+
+use super::{File, Stat, StatMode};
+use crate::drivers::block::BLOCK_DEVICE;
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use bitflags::bitflags;
+use easy_fs::{EasyFileSystem, Inode};
+use lazy_static::*;
+
+lazy_static! {
+    /// 根目录，所有文件都在根目录下
+    static ref ROOT_INODE: Arc<Inode> = {
+        let block_device = BLOCK_DEVICE.clone().expect("no block device to load applications from");
+        let efs = EasyFileSystem::open(block_device).expect("no easy-fs on the block device");
+        Arc::new(EasyFileSystem::root_inode(&efs))
+    };
+    /// 每个文件当前被多少个OSInode打开，打开着的文件不能删除
+    static ref OPEN_COUNTS: UPSafeCell<BTreeMap<String, usize>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// 读出名为`name`的应用的ELF文件，没有这个应用时返回None
+pub fn read_app(name: &str) -> Option<Vec<u8>> {
+    let inode = ROOT_INODE.find(name)?;
+    let mut data = vec![0u8; inode.size()];
+    let len = inode.read_at(0, &mut data);
+    data.truncate(len);
+    Some(data)
+}
+
+/// 打印根目录下的所有应用
+pub fn list_apps() {
+    println!("/**** APPS ****");
+    for app in ROOT_INODE.ls() {
+        println!("{}", app);
+    }
+    println!("**************/");
+}
+
+/// 打开的easy-fs文件，记录读写权限和当前的读写位置
+pub struct OSInode {
+    readable: bool,
+    writable: bool,
+    name: String,
+    inner: UPSafeCell<OSInodeInner>,
+}
+
+struct OSInodeInner {
+    offset: usize,
+    inode: Arc<Inode>,
+}
+
+impl OSInode {
+    pub fn new(readable: bool, writable: bool, name: &str, inode: Arc<Inode>) -> Self {
+        *OPEN_COUNTS
+            .exclusive_access()
+            .entry(String::from(name))
+            .or_insert(0) += 1;
+        Self {
+            readable,
+            writable,
+            name: String::from(name),
+            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+        }
+    }
+}
+
+impl Drop for OSInode {
+    fn drop(&mut self) {
+        let mut open_counts = OPEN_COUNTS.exclusive_access();
+        let count = open_counts.get_mut(&self.name).unwrap();
+        *count -= 1;
+        if *count == 0 {
+            open_counts.remove(&self.name);
+        }
+    }
+}
+
+bitflags! {
+    /// sys_open的flags，取值和Linux一致
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        /// 文件不存在时创建
+        const CREATE = 1 << 6;
+        /// 打开时把文件清空
+        const TRUNC = 1 << 9;
+    }
+}
+
+impl OpenFlags {
+    /// (可读, 可写)
+    pub fn read_write(&self) -> (bool, bool) {
+        if self.contains(Self::RDWR) {
+            (true, true)
+        } else if self.contains(Self::WRONLY) {
+            (false, true)
+        } else {
+            (true, false)
+        }
+    }
+}
+
+/// 按`flags`打开根目录下名为`name`的文件，文件不存在并且没有CREATE时返回None
+///
+/// 只有以可写方式打开时TRUNC才会清空文件
+pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+    let (readable, writable) = flags.read_write();
+    let inode = match ROOT_INODE.find(name) {
+        Some(inode) => {
+            if writable && flags.contains(OpenFlags::TRUNC) {
+                inode.clear();
+            }
+            inode
+        }
+        None if flags.contains(OpenFlags::CREATE) => ROOT_INODE.create(name)?,
+        None => return None,
+    };
+    Some(Arc::new(OSInode::new(readable, writable, name, inode)))
+}
+
+/// 删除根目录下名为`name`的文件，文件不存在或者还有任务打开着它时返回false
+pub fn unlink_file(name: &str) -> bool {
+    if OPEN_COUNTS.exclusive_access().contains_key(name) {
+        return false;
+    }
+    ROOT_INODE.remove(name)
+}
+
+impl File for OSInode {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut inner = self.inner.exclusive_access();
+        let mut total_read_size = 0;
+        for slice in buf.buffers.iter_mut() {
+            let read_size = inner.inode.read_at(inner.offset, slice);
+            inner.offset += read_size;
+            total_read_size += read_size;
+            // 到了文件末尾
+            if read_size < slice.len() {
+                break;
+            }
+        }
+        total_read_size
+    }
+
+    fn write(&self, buf: UserBuffer) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let mut total_write_size = 0;
+        for slice in buf.buffers.iter() {
+            let write_size = inner.inode.write_at(inner.offset, slice);
+            inner.offset += write_size;
+            total_write_size += write_size;
+            // 磁盘空间不足
+            if write_size < slice.len() {
+                break;
+            }
+        }
+        Some(total_write_size)
+    }
+
+    fn stat(&self) -> Stat {
+        let inner = self.inner.exclusive_access();
+        Stat {
+            mode: StatMode::FILE,
+            size: inner.inode.size() as u64,
+        }
+    }
+}
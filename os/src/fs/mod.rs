@@ -0,0 +1,56 @@
+//! File system
+//!
+//! 应用程序不再嵌入内核，而是由`easy-fs-fuse`打包进`fs.img`，挂载在virtio-blk上。
+//! 内核通过[`easy_fs`]打开块设备上的文件系统，按名字从根目录中读出应用的ELF。
+//!
+//! 每个任务有自己的文件描述符表，表中的每一项都是一个实现了[`File`]的对象：
+//! 标准输入输出（[`Stdin`]、[`Stdout`]）、easy-fs中的普通文件（[`OSInode`]）或者管道（[`Pipe`]）。
+
+mod inode;
+mod pipe;
+mod stdio;
+
+use crate::mm::UserBuffer;
+use bitflags::bitflags;
+
+/// 可以通过文件描述符读写的对象
+pub trait File: Send + Sync {
+    /// 是否可以读
+    fn readable(&self) -> bool;
+    /// 是否可以写
+    fn writable(&self) -> bool;
+    /// 读到用户缓冲区`buf`中，返回读到的字节数
+    fn read(&self, buf: UserBuffer) -> usize;
+    /// 把用户缓冲区`buf`中的内容写入，返回写入的字节数，一个字节都写不进去时（例如管道的读端都已经关闭）返回None
+    fn write(&self, buf: UserBuffer) -> Option<usize>;
+    /// 文件的类型和大小
+    fn stat(&self) -> Stat;
+}
+
+/// 文件的状态，和用户库中的定义保持一致
+#[repr(C)]
+#[derive(Debug)]
+pub struct Stat {
+    /// 文件类型
+    pub mode: StatMode,
+    /// 文件大小（字节），不是普通文件时为0
+    pub size: u64,
+}
+
+bitflags! {
+    /// 文件类型，取值和Linux中st_mode的类型部分一致
+    pub struct StatMode: u32 {
+        /// 管道
+        const FIFO = 0o010000;
+        /// 字符设备
+        const CHR = 0o020000;
+        /// 目录
+        const DIR = 0o040000;
+        /// 普通文件
+        const FILE = 0o100000;
+    }
+}
+
+pub use inode::{list_apps, open_file, read_app, unlink_file, OSInode, OpenFlags};
+pub use pipe::{make_pipe, Pipe};
+pub use stdio::{Stdin, Stdout};
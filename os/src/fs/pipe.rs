@@ -0,0 +1,197 @@
+//! 匿名管道
+//!
+//! 读端和写端是两个[`Pipe`]，共享同一个环形缓冲区。缓冲区通过弱引用记录两端是否还存在：
+//! 所有写端都被关闭后，读完剩余数据的读操作返回0（EOF）；所有读端都被关闭后，写操作不再等待，
+//! 一个字节都没有写入时返回错误。
+
+use super::{File, Stat, StatMode};
+use crate::mm::UserBuffer;
+use crate::sync::UPSafeCell;
+use crate::task::suspend_current_and_run_next;
+use alloc::sync::{Arc, Weak};
+
+/// 环形缓冲区的大小
+const RING_BUFFER_SIZE: usize = 32;
+
+/// 管道的一端
+pub struct Pipe {
+    readable: bool,
+    writable: bool,
+    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+}
+
+impl Pipe {
+    fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+        Self {
+            readable: true,
+            writable: false,
+            buffer,
+        }
+    }
+
+    fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+        Self {
+            readable: false,
+            writable: true,
+            buffer,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum RingBufferStatus {
+    Full,
+    Empty,
+    Normal,
+}
+
+/// 管道的环形缓冲区，head处读出，tail处写入
+pub struct PipeRingBuffer {
+    arr: [u8; RING_BUFFER_SIZE],
+    head: usize,
+    tail: usize,
+    status: RingBufferStatus,
+    read_end: Weak<Pipe>,
+    write_end: Weak<Pipe>,
+}
+
+impl PipeRingBuffer {
+    fn new() -> Self {
+        Self {
+            arr: [0; RING_BUFFER_SIZE],
+            head: 0,
+            tail: 0,
+            status: RingBufferStatus::Empty,
+            read_end: Weak::new(),
+            write_end: Weak::new(),
+        }
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        self.status = RingBufferStatus::Normal;
+        let c = self.arr[self.head];
+        self.head = (self.head + 1) % RING_BUFFER_SIZE;
+        if self.head == self.tail {
+            self.status = RingBufferStatus::Empty;
+        }
+        c
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        self.status = RingBufferStatus::Normal;
+        self.arr[self.tail] = byte;
+        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
+        if self.tail == self.head {
+            self.status = RingBufferStatus::Full;
+        }
+    }
+
+    /// 可以读出的字节数
+    fn available_read(&self) -> usize {
+        if self.status == RingBufferStatus::Empty {
+            0
+        } else if self.tail > self.head {
+            self.tail - self.head
+        } else {
+            self.tail + RING_BUFFER_SIZE - self.head
+        }
+    }
+
+    /// 还可以写入的字节数
+    fn available_write(&self) -> usize {
+        RING_BUFFER_SIZE - self.available_read()
+    }
+
+    /// 所有写端都已经关闭
+    fn all_write_ends_closed(&self) -> bool {
+        self.write_end.upgrade().is_none()
+    }
+
+    /// 所有读端都已经关闭
+    fn all_read_ends_closed(&self) -> bool {
+        self.read_end.upgrade().is_none()
+    }
+}
+
+/// 创建一个管道，返回(读端, 写端)
+pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new()) });
+    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
+    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
+    let mut ring_buffer = buffer.exclusive_access();
+    ring_buffer.read_end = Arc::downgrade(&read_end);
+    ring_buffer.write_end = Arc::downgrade(&write_end);
+    drop(ring_buffer);
+    (read_end, write_end)
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// 管道为空时让出cpu，读到至少一个字节或者所有写端都关闭后返回
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        assert!(self.readable());
+        let total: usize = buf.buffers.iter().map(|buffer| buffer.len()).sum();
+        let mut bytes = buf.buffers.iter_mut().flat_map(|buffer| buffer.iter_mut());
+        let mut read_size = 0;
+        while read_size < total {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            let available = ring_buffer.available_read();
+            if available == 0 {
+                if read_size > 0 || ring_buffer.all_write_ends_closed() {
+                    break;
+                }
+                // 切换之前必须释放缓冲区，写端需要访问它
+                drop(ring_buffer);
+                suspend_current_and_run_next();
+                continue;
+            }
+            for _ in 0..available.min(total - read_size) {
+                *bytes.next().unwrap() = ring_buffer.read_byte();
+                read_size += 1;
+            }
+        }
+        read_size
+    }
+
+    /// 管道满时让出cpu，全部写入或者所有读端都关闭后返回，读端关闭前一个字节都没有写入时返回None
+    fn write(&self, buf: UserBuffer) -> Option<usize> {
+        assert!(self.writable());
+        let total: usize = buf.buffers.iter().map(|buffer| buffer.len()).sum();
+        let mut bytes = buf.buffers.iter().flat_map(|buffer| buffer.iter());
+        let mut write_size = 0;
+        while write_size < total {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            if ring_buffer.all_read_ends_closed() {
+                if write_size == 0 {
+                    return None;
+                }
+                break;
+            }
+            let available = ring_buffer.available_write();
+            if available == 0 {
+                drop(ring_buffer);
+                suspend_current_and_run_next();
+                continue;
+            }
+            for _ in 0..available.min(total - write_size) {
+                ring_buffer.write_byte(*bytes.next().unwrap());
+                write_size += 1;
+            }
+        }
+        Some(write_size)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: StatMode::FIFO,
+            size: 0,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! 标准输入输出
+
+use super::{File, Stat, StatMode};
+use crate::console::{getchar, putchar};
+use crate::mm::UserBuffer;
+use crate::task::suspend_current_and_run_next;
+
+/// 标准输入，从串口读取
+pub struct Stdin;
+
+/// 标准输出，写到串口
+pub struct Stdout;
+
+impl File for Stdin {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn writable(&self) -> bool {
+        false
+    }
+
+    /// 至少等到一个字符，没有输入时让出cpu；之后把已经到达的字符尽量读满
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut read = 0;
+        for buffer in buf.buffers.iter_mut() {
+            for byte in buffer.iter_mut() {
+                let c = if read == 0 {
+                    loop {
+                        match getchar() {
+                            Some(c) => break c,
+                            None => suspend_current_and_run_next(),
+                        }
+                    }
+                } else {
+                    // 已经读到了字符，没有更多输入时直接返回
+                    match getchar() {
+                        Some(c) => c,
+                        None => return read,
+                    }
+                };
+                *byte = c;
+                read += 1;
+            }
+        }
+        read
+    }
+
+    fn write(&self, _buf: UserBuffer) -> Option<usize> {
+        None
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: StatMode::CHR,
+            size: 0,
+        }
+    }
+}
+
+impl File for Stdout {
+    fn readable(&self) -> bool {
+        false
+    }
+
+    fn writable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, _buf: UserBuffer) -> usize {
+        0
+    }
+
+    fn write(&self, buf: UserBuffer) -> Option<usize> {
+        let mut written = 0;
+        for buffer in buf.buffers.iter() {
+            for &byte in buffer.iter() {
+                putchar(byte);
+            }
+            written += buffer.len();
+        }
+        Some(written)
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: StatMode::CHR,
+            size: 0,
+        }
+    }
+}
@@ -12,8 +12,8 @@
 //! initialize various pieces of functionality. (See its source code for
 //! details.)
 //!
-//! We then call [`task::run_first_task()`] and for the first time go to
-//! userspace.
+//! We then call [`task::run_tasks()`], which goes to userspace for the first
+//! time and keeps scheduling tasks until all of them have exited.
 
 #![deny(missing_docs)]
 #![deny(warnings)]
@@ -33,8 +33,10 @@ mod board;
 #[macro_use]
 mod console;
 mod config;
+mod drivers;
+mod dtb;
+mod fs;
 mod lang_items;
-mod loader;
 mod mm;
 mod sbi;
 mod sync;
@@ -45,9 +47,6 @@ pub mod trap;
 
 // .asm 文件则通常是纯粹的原始汇编文件，不包含预处理器指令。这种文件直接包含原始的汇编指令，没有经过额外的处理或转换。
 global_asm!(include_str!("entry.asm"));
-// .S 文件中可以包含预处理指令（如 #include 和 #define），这意味着该文件将通过预处理器进行处理，并允许使用宏定义、
-// 条件编译等高级特性。预处理器将处理这些指令，并生成最终的汇编代码。
-global_asm!(include_str!("link_app.S"));
 
 /// 内核需要bss初始化为0,bss用于储存未初始化的全局或静态变量
 fn clear_bss() {
@@ -62,13 +61,23 @@ fn clear_bss() {
 }
 
 /// the rust entry-point of os
+///
+/// SBI通过a0传入当前hart的id，通过a1传入设备树的物理地址
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(_hart_id: usize, dtb_pa: usize) -> ! {
     clear_bss();
+
+    // 在开启分页之前读取设备树，后面的内存和时钟初始化都依赖它
+    dtb::init(dtb_pa);
     println!("[kernel] Hello, world! {}", timer::get_time_us());
 
     // 初始化内存分配相关的工作
     mm::init();
+    let usage = mm::frame_usage();
+    println!("[kernel] frames: {} used / {} total", usage.used, usage.total);
+
+    // 设备的MMIO区域在mm::init中映射
+    drivers::init();
 
     // println!("[kernel] back to world!");
     // mm::remap_test();
@@ -76,14 +85,13 @@ pub fn rust_main() -> ! {
     // 指定trap触发函数，开启S模式下的trap
     trap::init();
 
-    // loader::load_apps();
-
     // 防止S特权级时钟中断被屏蔽，需要进行初始化
     trap::enable_timer_interrupt();
+    trap::enable_external_interrupt();
 
     // 触发Trap::Interrupt(Interrupt::SupervisorTimer)，内部继续调用set_next_trigger，以达到10ms中断一次的效果
     timer::set_next_trigger();
 
-    task::run_first_task();
-    panic!("Unreachable in rust_main!");
+    fs::list_apps();
+    task::run_tasks();
 }
@@ -1,14 +1,16 @@
+use alloc::collections::BTreeSet;
+use alloc::vec;
 use alloc::vec::Vec;
 use lazy_static::*;
-use crate::config::MEMORY_END;
+use crate::dtb::memory_end;
 
 /// 物理页帧管理器
 use super::address::{PhysPageNum, PhysAddr};
 
 use crate::sync::UPSafeCell;
 lazy_static! {
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<StackFrameAllocator> = unsafe {
-        UPSafeCell::new(StackFrameAllocator::new())
+    pub static ref FRAME_ALLOCATOR: UPSafeCell<BuddyFrameAllocator> = unsafe {
+        UPSafeCell::new(BuddyFrameAllocator::new())
     };
 }
 
@@ -16,74 +18,158 @@ trait FrameAllocator {
     fn new() -> Self;
     /// 分配一个物理页
     fn alloc(&mut self) -> Option<PhysPageNum>;
+    /// 分配`count`个物理上连续的物理页，起始物理页号按`align`页对齐
+    fn alloc_contiguous(&mut self, count: usize, align: usize) -> Option<PhysPageNum>;
     /// 释放目标物理页
     fn dealloc(&mut self, ppn: PhysPageNum);
 }
 
-/// 【current, end)，代表此前从未被分配出去过的物理页
-pub struct StackFrameAllocator {
-    // 空闲内存的起始物理页编号
-    current: usize,
-    // 空闲内存的结束物理页号
+/// 最大的块为2^(MAX_ORDER - 1)个物理页
+const MAX_ORDER: usize = 32;
+
+/// 伙伴系统：大小为2^k页的空闲块都按2^k页对齐，回收时和相邻的伙伴块合并
+pub struct BuddyFrameAllocator {
+    // 管理的物理页号范围[start, end)
+    start: usize,
     end: usize,
-    // 保存了被回收的物理页编号
-    recycled: Vec<usize>, 
+    // free_lists[k]保存所有大小为2^k页的空闲块的起始物理页号
+    free_lists: Vec<BTreeSet<usize>>,
+    // 每个物理页占一位，1代表已分配，用来检测重复回收
+    allocated: Vec<u64>,
+    // 已分配出去的物理页数量
+    used: usize,
 }
 
-impl FrameAllocator for StackFrameAllocator {
+impl FrameAllocator for BuddyFrameAllocator {
     fn new() -> Self {
         Self {
-            current: 0,
+            start: 0,
             end: 0,
-            recycled: Vec::new(),
+            free_lists: (0..MAX_ORDER).map(|_| BTreeSet::new()).collect(),
+            allocated: Vec::new(),
+            used: 0,
         }
     }
 
     fn alloc(&mut self) -> Option<PhysPageNum> {
-        // 从已被回收过的内存中再进行分配
-        if let Some(ppn) = self.recycled.pop() {
-            // todo考虑是否清0
-            Some(ppn.into())
-        } else {
-            // 无法分配，内存范围外
-            if self.current == self.end {
-                None
-            } else {
-                self.current += 1;
-                Some((self.current - 1).into())
-            }
+        let ppn = self.alloc_block(0)?;
+        self.mark(ppn, true);
+        self.used += 1;
+        Some(ppn.into())
+    }
+
+    fn alloc_contiguous(&mut self, count: usize, align: usize) -> Option<PhysPageNum> {
+        assert!(count > 0 && align.is_power_of_two());
+        // 块按自身大小对齐，块足够大时对齐要求自然满足
+        let size = count.max(align).next_power_of_two();
+        let start = self.alloc_block(size.trailing_zeros() as usize)?;
+        // 多出来的部分还给伙伴系统
+        for ppn in start + count..start + size {
+            self.free_block(ppn, 0);
+        }
+        for ppn in start..start + count {
+            self.mark(ppn, true);
         }
+        self.used += count;
+        Some(start.into())
     }
 
     fn dealloc(&mut self, ppn: PhysPageNum) {
         let ppn = ppn.0;
 
         // 检测是否已经被回收过
-        if ppn >= self.current || self.recycled.iter().find(|&v| { *v == ppn }).is_some() {
+        if ppn < self.start || ppn >= self.end || !self.is_allocated(ppn) {
             panic!("Frame ppn={:#x} has not been allocated!", ppn);
         }
 
-        self.recycled.push(ppn);
+        self.mark(ppn, false);
+        self.used -= 1;
+        self.free_block(ppn, 0);
     }
 }
 
-impl StackFrameAllocator {
+impl BuddyFrameAllocator {
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
-        self.current = l.0;
+        self.start = l.0;
         self.end = r.0;
+        self.allocated = vec![0; (r.0 - l.0 + 63) / 64];
+        self.used = 0;
+        // 把[l, r)切成尽可能大的对齐块
+        let mut current = l.0;
+        while current < r.0 {
+            let mut order = (current.trailing_zeros() as usize).min(MAX_ORDER - 1);
+            while current + (1 << order) > r.0 {
+                order -= 1;
+            }
+            self.free_lists[order].insert(current);
+            current += 1 << order;
+        }
+    }
+
+    /// 取出一个大小为2^order页的空闲块，必要时把更大的块对半拆开
+    fn alloc_block(&mut self, order: usize) -> Option<usize> {
+        let mut k = (order..MAX_ORDER).find(|&k| !self.free_lists[k].is_empty())?;
+        let block = *self.free_lists[k].iter().next().unwrap();
+        self.free_lists[k].remove(&block);
+        while k > order {
+            k -= 1;
+            self.free_lists[k].insert(block + (1 << k));
+        }
+        Some(block)
+    }
+
+    /// 放回一个空闲块，伙伴也空闲时合并成更大的块
+    fn free_block(&mut self, mut block: usize, mut order: usize) {
+        while order < MAX_ORDER - 1 {
+            let buddy = block ^ (1 << order);
+            if !self.free_lists[order].remove(&buddy) {
+                break;
+            }
+            block = block.min(buddy);
+            order += 1;
+        }
+        self.free_lists[order].insert(block);
+    }
+
+    fn is_allocated(&self, ppn: usize) -> bool {
+        let idx = ppn - self.start;
+        self.allocated[idx / 64] & (1 << (idx % 64)) != 0
+    }
+
+    fn mark(&mut self, ppn: usize, allocated: bool) {
+        let idx = ppn - self.start;
+        if allocated {
+            self.allocated[idx / 64] |= 1 << (idx % 64);
+        } else {
+            self.allocated[idx / 64] &= !(1 << (idx % 64));
+        }
+    }
+
+    pub fn usage(&self) -> FrameUsage {
+        FrameUsage {
+            total: self.end - self.start,
+            used: self.used,
+        }
     }
 }
 
+/// 物理页的使用情况，用于诊断
+#[derive(Copy, Clone, Debug)]
+pub struct FrameUsage {
+    pub total: usize,
+    pub used: usize,
+}
+
 pub fn init_frame_allocator() {
     extern "C" {
         // 内核内存边界
         fn ekernel();
     }
 
-    // 内存：内核 - qemu总内存限制在8mb
+    // 内存：内核 - 设备树中给出的内存结束地址
     FRAME_ALLOCATOR
         .exclusive_access()
-        .init(PhysAddr::from(ekernel as usize).ceil(), PhysAddr::from(MEMORY_END).floor());
+        .init(PhysAddr::from(ekernel as usize).ceil(), PhysAddr::from(memory_end()).floor());
 }
 
 pub struct FrameTracker {
@@ -117,6 +203,25 @@ pub fn frame_alloc() -> Option<FrameTracker> {
         .map(|ppn| FrameTracker::new(ppn))
 }
 
+/// 申请`count`个物理上连续的物理页，起始物理页号按`align`页对齐，供DMA等场景使用
+///
+/// 每个物理页各自对应一个FrameTracker，可以单独回收
+pub fn frame_alloc_contiguous(count: usize, align: usize) -> Option<Vec<FrameTracker>> {
+    let start = FRAME_ALLOCATOR
+        .exclusive_access()
+        .alloc_contiguous(count, align)?;
+    Some(
+        (start.0..start.0 + count)
+            .map(|ppn| FrameTracker::new(ppn.into()))
+            .collect(),
+    )
+}
+
+/// 当前物理页的使用情况
+pub fn frame_usage() -> FrameUsage {
+    FRAME_ALLOCATOR.exclusive_access().usage()
+}
+
 fn frame_dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR
         .exclusive_access()
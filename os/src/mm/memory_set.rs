@@ -1,4 +1,5 @@
-use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
+use crate::config::{PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_BASE, USER_STACK_SIZE};
+use crate::dtb::machine_info;
 use crate::sync::UPSafeCell;
 
 use super::address::{PhysAddr, PhysPageNum, StepByOne, VPNRange, VirtAddr, VirtPageNum};
@@ -13,6 +14,9 @@ use bitflags::bitflags;
 use lazy_static::*;
 use riscv::register::satp;
 
+/// ELF文件头中RISC-V的e_machine
+const EM_RISCV: u16 = 243;
+
 extern "C" {
     /// 内核中的内存布局 .stext段地址
     fn stext();
@@ -40,9 +44,38 @@ pub struct MapArea {
     /// 虚拟页号的连续区间
     vpn_range: VPNRange,
     /// BTreeMap键值对容器，vpn -> ppn 映射
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// 物理页通过Arc计数，fork后父子任务的用户页面共享同一个FrameTracker，直到一方写入（写时复制）
+    data_frames: BTreeMap<VirtPageNum, Arc<FrameTracker>>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// 懒加载逻辑段的初始内容，缺页时才复制进物理页
+    lazy_data: Option<LazyData>,
+}
+
+/// 懒加载逻辑段的初始内容
+///
+/// ELF从磁盘读出后只保留各段的文件内容，fork和拆分逻辑段时共享同一份数据
+#[derive(Clone)]
+struct LazyData {
+    /// 数据相对逻辑段第一页页首的偏移
+    offset: usize,
+    data: Arc<[u8]>,
+    /// 只有data[start..]属于这个逻辑段，拆分后的后半段需要跳过前面的部分
+    start: usize,
+}
+
+impl LazyData {
+    fn new(offset: usize, data: &[u8]) -> Self {
+        Self {
+            offset,
+            data: Arc::from(data),
+            start: 0,
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.data[self.start..]
+    }
 }
 
 impl MapArea {
@@ -61,6 +94,18 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            lazy_data: None,
+        }
+    }
+
+    /// 复制另一个逻辑段的范围和权限，但不复制物理页，fork时使用
+    pub fn from_another(another: &MapArea) -> Self {
+        Self {
+            vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
+            data_frames: BTreeMap::new(),
+            map_type: another.map_type,
+            map_perm: another.map_perm,
+            lazy_data: another.lazy_data.clone(),
         }
     }
 
@@ -86,8 +131,113 @@ impl MapArea {
         }
     }
 
-    /// 建立vpn与ppn映射，ppn需要申请内存
-    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+    /// 从`at`处把逻辑段一分为二，自己保留[start, at)，返回[at, end)
+    pub fn split_off(&mut self, at: VirtPageNum) -> MapArea {
+        assert!(self.vpn_range.get_start() < at && at < self.vpn_range.get_end());
+        // 懒加载的内容以第一页页首为起点，后半段需要往后挪
+        let lazy_data = self.lazy_data.as_ref().map(|lazy| {
+            let shift = (at.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+            if shift <= lazy.offset {
+                LazyData {
+                    offset: lazy.offset - shift,
+                    ..lazy.clone()
+                }
+            } else {
+                LazyData {
+                    offset: 0,
+                    data: lazy.data.clone(),
+                    start: (lazy.start + shift - lazy.offset).min(lazy.data.len()),
+                }
+            }
+        });
+        let right = MapArea {
+            vpn_range: VPNRange::new(at, self.vpn_range.get_end()),
+            data_frames: self.data_frames.split_off(&at),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+            lazy_data,
+        };
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), at);
+        right
+    }
+
+    /// vpn是否在该逻辑段内
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.vpn_range.get_start() <= vpn && vpn < self.vpn_range.get_end()
+    }
+
+    /// 懒加载逻辑段第一次访问`vpn`时调用：申请物理页，填入初始内容并建立映射
+    ///
+    /// 物理页耗尽时返回false
+    pub fn lazy_map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        assert_eq!(self.map_type, MapType::Lazy);
+        // FrameTracker::new已经把物理页清0，bss和栈不需要额外处理
+        let frame = match frame_alloc() {
+            Some(frame) => frame,
+            None => return false,
+        };
+        if let Some(lazy) = &self.lazy_data {
+            let (offset, data) = (lazy.offset, lazy.bytes());
+            // 该页在逻辑段内的字节范围，换算成data中的范围
+            let page_start = (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+            let src_start = page_start.saturating_sub(offset).min(data.len());
+            let src_end = (page_start + PAGE_SIZE).saturating_sub(offset).min(data.len());
+            // 超出文件内容的部分（如.bss）保持为0
+            if src_start < src_end {
+                let dst_start = offset + src_start - page_start;
+                frame.ppn.get_bytes_array()[dst_start..dst_start + src_end - src_start]
+                    .copy_from_slice(&data[src_start..src_end]);
+            }
+        }
+        if !page_table.map(vpn, frame.ppn, PTEFlags::from_bits(self.map_perm.bits).unwrap()) {
+            return false;
+        }
+        self.data_frames.insert(vpn, Arc::new(frame));
+        true
+    }
+
+    /// 以只读方式映射一个共享的物理页，fork时父子任务的同一页面都会调用
+    ///
+    /// 物理页耗尽时返回false
+    fn map_shared_one(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        frame: Arc<FrameTracker>,
+    ) -> bool {
+        let pte_flags = PTEFlags::from_bits((self.map_perm - MapPermission::W).bits).unwrap();
+        if !page_table.map(vpn, frame.ppn, pte_flags) {
+            return false;
+        }
+        self.data_frames.insert(vpn, frame);
+        true
+    }
+
+    /// 写时复制：给`vpn`一个私有的物理页并恢复写权限
+    ///
+    /// 物理页耗尽时返回false，页面保持共享和只读
+    pub fn cow_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let frame = self.data_frames.get(&vpn).unwrap();
+        // 还有其他任务共享这一页，复制出一份自己独占的
+        if Arc::strong_count(frame) > 1 {
+            let new_frame = match frame_alloc() {
+                Some(frame) => frame,
+                None => return false,
+            };
+            new_frame
+                .ppn
+                .get_bytes_array()
+                .copy_from_slice(frame.ppn.get_bytes_array());
+            self.data_frames.insert(vpn, Arc::new(new_frame));
+        }
+        // 到这里自己独占这一页（刚复制出来的，或者其他任务已经复制走了），直接恢复写权限即可
+        // 页表项已经存在，不需要再申请节点
+        let ppn = self.data_frames.get(&vpn).unwrap().ppn;
+        page_table.map(vpn, ppn, PTEFlags::from_bits(self.map_perm.bits).unwrap())
+    }
+
+    /// 建立vpn与ppn映射，ppn需要申请内存，物理页耗尽时返回false
+    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
         let ppn: PhysPageNum;
         match self.map_type {
             // 恒等映射
@@ -98,17 +248,23 @@ impl MapArea {
 
             // 随机映射，额外申请多一个实际的ppn地址
             MapType::Framed => {
-                let frame = frame_alloc().unwrap();
+                let frame = match frame_alloc() {
+                    Some(frame) => frame,
+                    None => return false,
+                };
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                self.data_frames.insert(vpn, Arc::new(frame));
             }
+
+            // 懒分配，等到缺页异常时再调用lazy_map_one
+            MapType::Lazy => return true,
         }
 
         // 创建pte标识为
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
 
         // 通过vpn寻找或创建pte，即pte地址上保存了ppn
-        page_table.map(vpn, ppn, pte_flags);
+        page_table.map(vpn, ppn, pte_flags)
     }
 
     #[allow(unused)]
@@ -121,23 +277,38 @@ impl MapArea {
     
     #[allow(unused)]
     pub fn append_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        // 堆是懒加载逻辑段，这里不会申请物理页
         for vpn in VPNRange::new(self.vpn_range.get_end(), new_end) {
-            self.map_one(page_table, vpn)
+            self.map_one(page_table, vpn);
         }
         self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
     }
 
     #[allow(unused)]
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        if self.map_type == MapType::Framed {
-            self.data_frames.remove(&vpn);
+        match self.map_type {
+            MapType::Framed => {
+                self.data_frames.remove(&vpn);
+            }
+            // 从未被访问过的页面没有映射，不需要处理
+            MapType::Lazy => {
+                if self.data_frames.remove(&vpn).is_none() {
+                    return;
+                }
+            }
+            MapType::Identical => {}
         }
         page_table.unmap(vpn);
     }
 
-    pub fn map(&mut self, page_table: &mut PageTable) {
+    /// 映射整个逻辑段，物理页耗尽时返回false，已经建立的映射由调用者撤销
+    pub fn map(&mut self, page_table: &mut PageTable) -> bool {
+        self.vpn_range.into_iter().all(|vpn| self.map_one(page_table, vpn))
+    }
+
+    pub fn unmap(&mut self, page_table: &mut PageTable) {
         for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+            self.unmap_one(page_table, vpn);
         }
     }
 }
@@ -149,6 +320,8 @@ pub enum MapType {
     Identical,
     /// 虚地址与物理地址的映射关系是相对随机的
     Framed,
+    /// 和Framed一样，但物理页在第一次访问触发缺页异常时才分配
+    Lazy,
 }
 
 bitflags! {
@@ -168,12 +341,12 @@ pub struct MemorySet {
 }
 
 impl MemorySet {
-    /// 初始化，创建一个新的地址空间
-    pub fn new_bare() -> Self {
-        Self {
-            page_table: PageTable::new(),
+    /// 初始化，创建一个新的地址空间，物理页耗尽时返回None
+    pub fn new_bare() -> Option<Self> {
+        Some(Self {
+            page_table: PageTable::new()?,
             areas: Vec::new(),
-        }
+        })
     }
 
     // todo
@@ -186,40 +359,209 @@ impl MemorySet {
         self.page_table.translate(vpn)
     }
 
+    /// 通过应用地址空间复制出一个新的地址空间，fork时使用
+    ///
+    /// 用户可访问的页面不会被复制，而是在父子两边都改成只读并共享同一个物理页，
+    /// 任何一方写入时触发缺页，由[`MemorySet::handle_page_fault`]复制出私有的页面
+    ///
+    /// 物理页耗尽时返回None，已经改成只读的父任务页面在写入时会重新恢复写权限
+    pub fn from_existed_user(user_space: &mut MemorySet) -> Option<MemorySet> {
+        let mut memory_set = Self::new_bare()?;
+        // 跳板不在areas中，需要单独映射
+        if !memory_set.map_trampoline() {
+            return None;
+        }
+        for area in user_space.areas.iter_mut() {
+            let mut new_area = MapArea::from_another(area);
+            if area.map_perm.contains(MapPermission::U) {
+                // 懒加载逻辑段中还没有分配过的页面保持未映射，各自缺页时再加载
+                let shared: Vec<_> = area
+                    .data_frames
+                    .iter()
+                    .map(|(&vpn, frame)| (vpn, frame.clone()))
+                    .collect();
+                for (vpn, frame) in shared {
+                    // 父任务的页表项已经存在，只是去掉写权限，不会申请物理页
+                    area.map_shared_one(&mut user_space.page_table, vpn, frame.clone());
+                    if !new_area.map_shared_one(&mut memory_set.page_table, vpn, frame) {
+                        return None;
+                    }
+                }
+                memory_set.areas.push(new_area);
+            } else {
+                // Trap上下文由内核直接通过物理页访问，不能共享，逐页复制
+                if !memory_set.push(new_area, None) {
+                    return None;
+                }
+                for &vpn in area.data_frames.keys() {
+                    let src_ppn = user_space.page_table.translate(vpn).unwrap().ppn();
+                    let dst_ppn = memory_set.page_table.translate(vpn).unwrap().ppn();
+                    dst_ppn
+                        .get_bytes_array()
+                        .copy_from_slice(src_ppn.get_bytes_array());
+                }
+            }
+        }
+        Some(memory_set)
+    }
+
+    /// 回收所有逻辑段的物理页，页表本身的物理页在MemorySet被drop时回收
+    pub fn recycle_data_pages(&mut self) {
+        self.areas.clear();
+    }
+
+    /// 移除以`start_vpn`开头的逻辑段，解除映射并回收物理页
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
+        if let Some((idx, area)) = self
+            .areas
+            .iter_mut()
+            .enumerate()
+            .find(|(_, area)| area.vpn_range.get_start() == start_vpn)
+        {
+            area.unmap(&mut self.page_table);
+            self.areas.remove(idx);
+        }
+    }
+
+    /// 处理用户态在`va`上的缺页异常，`access`为这次访问需要的权限
+    ///
+    /// 可以处理两种情况：懒加载逻辑段中还没有分配物理页的页面，以及对写时复制页面的写入，
+    /// 返回false代表非法访问或物理页耗尽
+    pub fn handle_page_fault(&mut self, va: VirtAddr, access: MapPermission) -> bool {
+        let vpn = va.floor();
+        let page_table = &mut self.page_table;
+        if let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.map_perm.contains(MapPermission::U) && area.contains(vpn))
+        {
+            if !area.map_perm.contains(access) {
+                return false;
+            }
+            if area.data_frames.contains_key(&vpn) {
+                // 页面已经映射，只有逻辑段可写而页表项只读时才是写时复制
+                let writable = page_table.translate(vpn).unwrap().writable();
+                if !access.contains(MapPermission::W) || writable {
+                    return false;
+                }
+                return area.cow_one(page_table, vpn);
+            }
+            if area.map_type != MapType::Lazy {
+                return false;
+            }
+            area.lazy_map_one(page_table, vpn)
+        } else {
+            false
+        }
+    }
+
+    /// 插入一个由用户申请的懒加载逻辑段，与已有逻辑段重叠或越过TRAP_CONTEXT时返回false
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) -> bool {
+        let (start, end) = (start_va.floor(), end_va.ceil());
+        if start >= end || end > VirtAddr::from(TRAP_CONTEXT).floor() {
+            return false;
+        }
+        if self.areas.iter().any(|area| {
+            area.vpn_range.get_start() < end && start < area.vpn_range.get_end()
+        }) {
+            return false;
+        }
+        self.push(MapArea::new(start_va, end_va, MapType::Lazy, permission), None)
+    }
+
+    /// 解除[start_va, end_va)的映射，部分落在范围内的逻辑段会被拆分
+    ///
+    /// 范围内的每一页都必须属于某个逻辑段，且不能碰到TRAP_CONTEXT，否则什么都不做并返回false
+    pub fn remove_area_range(&mut self, start_va: VirtAddr, end_va: VirtAddr) -> bool {
+        let (start, end) = (start_va.floor(), end_va.ceil());
+        if start >= end || end > VirtAddr::from(TRAP_CONTEXT).floor() {
+            return false;
+        }
+        // 逻辑段之间不重叠，交集的页数之和等于范围的页数就说明每一页都有映射
+        let covered: usize = self
+            .areas
+            .iter()
+            .map(|area| {
+                let l = area.vpn_range.get_start().max(start);
+                let r = area.vpn_range.get_end().min(end);
+                r.0.saturating_sub(l.0)
+            })
+            .sum();
+        if covered != end.0 - start.0 {
+            return false;
+        }
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            let area = &mut self.areas[idx];
+            let (l, r) = (area.vpn_range.get_start(), area.vpn_range.get_end());
+            if r <= start || end <= l {
+                idx += 1;
+                continue;
+            }
+            // 范围右侧剩下的部分拆成新的逻辑段，留到下一轮处理
+            if end < r {
+                let right = area.split_off(end);
+                self.areas.insert(idx + 1, right);
+            }
+            let area = &mut self.areas[idx];
+            if start <= l {
+                area.unmap(&mut self.page_table);
+                self.areas.remove(idx);
+            } else {
+                let mut middle = area.split_off(start);
+                middle.unmap(&mut self.page_table);
+                idx += 1;
+            }
+        }
+        true
+    }
+
     /// 把data内容推进map_area，连续空间段中，page_table的作用为寻找连续空间段的位置
-    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
-        map_area.map(&mut self.page_table);
+    ///
+    /// 物理页耗尽时撤销这个逻辑段已经建立的映射并返回false
+    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) -> bool {
+        if !map_area.map(&mut self.page_table) {
+            map_area.unmap(&mut self.page_table);
+            return false;
+        }
         if let Some(data) = data {
             map_area.copy_data(&mut self.page_table, data);
         }
         self.areas.push(map_area);
+        true
     }
 
     /// Mention that trampoline is not collected by areas.
-    fn map_trampoline(&mut self) {
+    fn map_trampoline(&mut self) -> bool {
         self.page_table.map(
             VirtAddr::from(TRAMPOLINE).into(),
             PhysAddr::from(strampoline as usize).into(),
             PTEFlags::R | PTEFlags::X,
-        );
+        )
     }
 
     #[allow(unused)]
-    /// framed方式插入
+    /// framed方式插入，物理页耗尽时返回false
     pub fn insert_framed_area(
         &mut self,
         start_va: VirtAddr,
         end_va: VirtAddr,
         permission: MapPermission,
-    ) {
+    ) -> bool {
         self.push(
             MapArea::new(start_va, end_va, MapType::Framed, permission),
             None,
-        );
+        )
     }
 
+    /// 启动时物理页充足，内核地址空间的映射不会失败
     pub fn new_kernel() -> Self {
-        let mut memory_set = Self::new_bare();
+        let mut memory_set = Self::new_bare().expect("no memory for kernel page table");
         // 跳板初始化
         memory_set.map_trampoline();
         // 内核section
@@ -270,16 +612,30 @@ impl MemorySet {
             ),
             None,
         );
+        let machine = machine_info();
         println!("mapping physical memory");
         memory_set.push(
             MapArea::new(
                 (ekernel as usize).into(),
-                MEMORY_END.into(),
+                machine.memory.end().into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
             ),
             None,
         );
+        // 设备的MMIO区域，同样恒等映射
+        println!("mapping memory-mapped registers");
+        for region in machine.mmio_regions() {
+            memory_set.push(
+                MapArea::new(
+                    region.base.into(),
+                    region.end().into(),
+                    MapType::Identical,
+                    MapPermission::R | MapPermission::W,
+                ),
+                None,
+            );
+        }
         memory_set
     }
 
@@ -298,15 +654,20 @@ impl MemorySet {
     /// (... 一共map_area个三级pte)
     /// 4kb 用户栈
     /// 4kb 用户栈
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    ///
+    /// ELF来自磁盘上的文件，内容不可信：不是RISC-V的64位ELF、程序头损坏、段低于USER_BASE、
+    /// 越过TRAP_CONTEXT或者互相重叠时返回None，物理页耗尽时同样返回None
+    pub fn from_elf(elf_data: &[u8]) -> Option<(Self, usize, usize)> {
         // 申请了一个root_ppn, 4kb，即一个frame_tracker
-        let mut memory_set = Self::new_bare();
+        let mut memory_set = Self::new_bare()?;
 
         // 申请跳板内存，TRAMPOLINE作为虚拟内存映射strampoline
-        memory_set.map_trampoline();
+        if !memory_set.map_trampoline() {
+            return None;
+        }
 
         // 利用xmas_elf工具处理elf数据
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
+        let elf = xmas_elf::ElfFile::new(elf_data).ok()?;
 
         // 判断elf是否合法
         // Magic: (7F 45 4C 46)
@@ -314,19 +675,48 @@ impl MemorySet {
         // 通常会查看 该位置的值是否正确，来快速确认被加载的文件是不是一个 ELF
         let elf_header = elf.header;
         let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
+        if magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return None;
+        }
+        // 只接受小端的64位RISC-V ELF，e_machine在文件头的第18、19字节
+        if elf_header.pt1.class() != xmas_elf::header::Class::SixtyFour
+            || elf_data.get(18..20)? != EM_RISCV.to_le_bytes()
+        {
+            return None;
+        }
+
+        // 应用的段只能放在TRAP_CONTEXT之下，这里用截断到39位后的地址，和页表中的位置一致
+        let user_end = VirtAddr::from(TRAP_CONTEXT).0;
 
         // 得到 program header 的数目，然后遍历所有的 program header 并将合适的区域加入到应用地址空间中
         let ph_count = elf_header.pt2.ph_count();
+        // xmas-elf按偏移直接引用程序头，越界或者没有按8字节对齐时会panic，先检查整个程序头表
+        let ph_offset = elf_header.pt2.ph_offset() as usize;
+        let ph_entry_size = elf_header.pt2.ph_entry_size() as usize;
+        let ph_table_end = (ph_count as usize)
+            .checked_mul(ph_entry_size)?
+            .checked_add(ph_offset)?;
+        if ph_entry_size != core::mem::size_of::<xmas_elf::program::ProgramHeader64>()
+            || ph_offset % 8 != 0
+            || ph_table_end > elf_data.len()
+        {
+            return None;
+        }
         let mut max_end_vpn = VirtPageNum(0);
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
+            let ph = elf.program_header(i).ok()?;
 
             // 类型为Load，则表示有必要被加载到内核中
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                // 计算区域在应用地址空间中的位置
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+            if ph.get_type() == Ok(xmas_elf::program::Type::Load) {
+                // 计算区域在应用地址空间中的位置，先检查原始地址，避免转换成VirtAddr时被截断
+                let start = ph.virtual_addr() as usize;
+                let end = start.checked_add(ph.mem_size() as usize)?;
+                // [0, USER_BASE)不映射，用于捕获空指针
+                if start < USER_BASE || end > user_end || ph.file_size() > ph.mem_size() {
+                    return None;
+                }
+                let start_va: VirtAddr = start.into();
+                let end_va: VirtAddr = end.into();
 
                 // 区域访问方式
                 // Program Header 提供了 ELF 文件在内存中加载和执行所需的关键信息，它是操作系统加载可执行文件的重要依据。
@@ -346,41 +736,64 @@ impl MemorySet {
                 // 这里如果并发的话，一定要管内存相对位置的，不然虚拟地址都是同一个，用了同一块物理内存，就会导致程序错误
 
                 // 为应用申请一段连续内存段（并没有实际分配）
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
-
-                // 向下取整后的end_va
-                max_end_vpn = map_area.vpn_range.get_end();
+                let mut map_area = MapArea::new(start_va, end_va, MapType::Lazy, map_perm);
+                let (l, r) = (map_area.vpn_range.get_start(), map_area.vpn_range.get_end());
+                if memory_set.areas.iter().any(|area| {
+                    area.vpn_range.get_start() < r && l < area.vpn_range.get_end()
+                }) {
+                    return None;
+                }
 
-                // 分配实际内存
-                memory_set.push(
-                    map_area,
-                    // header
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                // 向上取整后的end_va，段不一定按地址顺序排列，取最大的
+                max_end_vpn = max_end_vpn.max(map_area.vpn_range.get_end());
+
+                // 只保存段的内容，访问时才分配实际内存
+                let data_start = ph.offset() as usize;
+                let data_end = data_start.checked_add(ph.file_size() as usize)?;
+                map_area.lazy_data = Some(LazyData::new(
+                    start_va.page_offset(),
+                    elf.input.get(data_start..data_end)?,
+                ));
+                memory_set.push(map_area, None);
             }
         }
 
         // map user stack with U flags
         let max_end_va: VirtAddr = max_end_vpn.into();
-        let mut user_stack_bottom: usize = max_end_va.into();
+        // 不做符号扩展，和user_end在同一个范围内比较
+        let mut user_stack_bottom: usize = max_end_va.0;
 
         // 保护页面 4kb
         user_stack_bottom += PAGE_SIZE;
 
         // 建立用户栈
         let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        if user_stack_top > user_end {
+            return None;
+        }
         memory_set.push(
             MapArea::new(
                 user_stack_bottom.into(),
                 user_stack_top.into(),
-                MapType::Framed,
+                MapType::Lazy,
                 MapPermission::R | MapPermission::W | MapPermission::U,
             ),
             None,
         );
 
-        // 存放trap上下文，整个跳板下方4kb是trap
+        // 堆紧挨着用户栈顶，一开始为空，sbrk通过append_to/shrink_to改变它的大小
         memory_set.push(
+            MapArea::new(
+                user_stack_top.into(),
+                user_stack_top.into(),
+                MapType::Lazy,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
+
+        // 存放trap上下文，整个跳板下方4kb是trap
+        if !memory_set.push(
             MapArea::new(
                 TRAP_CONTEXT.into(),
                 TRAMPOLINE.into(),
@@ -388,16 +801,18 @@ impl MemorySet {
                 MapPermission::R | MapPermission::W,
             ),
             None,
-        );
+        ) {
+            return None;
+        }
 
-        (
+        Some((
             // 地址空间
             memory_set,
             // 用户栈虚拟地址
             user_stack_top,
             // 应用入口地址
             elf.header.pt2.entry_point() as usize,
-        )
+        ))
     }
 
     // 分页模式激活
@@ -425,18 +840,30 @@ impl MemorySet {
         }
     }
 
+    /// 把以`start`开头的逻辑段扩展到`new_end`，扩展的部分不能和其它逻辑段重叠，也不能越过TRAP_CONTEXT
     #[allow(unused)]
     pub fn append_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
-        if let Some(area) = self
+        let (start, new_end) = (start.floor(), new_end.ceil());
+        let idx = match self
             .areas
-            .iter_mut()
-            .find(|area| area.vpn_range.get_start() == start.floor())
+            .iter()
+            .position(|area| area.vpn_range.get_start() == start)
         {
-            area.append_to(&mut self.page_table, new_end.ceil());
-            true
-        } else {
-            false
+            Some(idx) => idx,
+            None => return false,
+        };
+        let old_end = self.areas[idx].vpn_range.get_end();
+        if new_end > VirtAddr::from(TRAP_CONTEXT).floor() {
+            return false;
+        }
+        // 堆向上增长时可能碰到mmap出来的区域
+        if self.areas.iter().enumerate().any(|(i, area)| {
+            i != idx && area.vpn_range.get_start() < new_end && old_end < area.vpn_range.get_end()
+        }) {
+            return false;
         }
+        self.areas[idx].append_to(&mut self.page_table, new_end);
+        true
     }
 }
 
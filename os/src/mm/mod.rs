@@ -7,7 +7,14 @@ mod memory_set;
 pub use memory_set::KERNEL_SPACE;
 pub use address::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum, StepByOne};
 pub use memory_set::{MapPermission, MemorySet};
-pub use page_table::{translated_byte_buffer, PageTableEntry};
+pub use frame_allocator::{frame_alloc_contiguous, FrameTracker};
+pub use frame_allocator::frame_usage;
+pub use page_table::{
+    copy_to_user,
+    translated_byte_buffer, translated_byte_buffer_mut, translated_ref, translated_refmut,
+    translated_str,
+    PageTableEntry, UserBuffer,
+};
 
 pub fn init() {
     // 内核初始化堆
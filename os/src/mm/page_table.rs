@@ -1,7 +1,11 @@
 /// 页表项
 
 use bitflags::*;
-use super::{address::{PhysPageNum, VirtPageNum, VirtAddr, StepByOne}, frame_allocator::{FrameTracker, frame_alloc}};
+use super::{address::{PhysAddr, PhysPageNum, VirtPageNum, VirtAddr, StepByOne}, frame_allocator::{FrameTracker, frame_alloc}};
+use super::MapPermission;
+use crate::config::PAGE_SIZE;
+use crate::task::handle_page_fault;
+use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 
@@ -72,6 +76,11 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+
+    /// 检测U特权级能否访问
+    pub fn is_user(&self) -> bool {
+        (self.flags() & PTEFlags::U) != PTEFlags::empty()
+    }
 }
 
 /// 页表节点
@@ -85,12 +94,13 @@ pub struct PageTable {
 }
 
 impl PageTable {
-    pub fn new() -> Self {
-        let frame = frame_alloc().unwrap();
-        PageTable {
+    /// 申请根节点，物理页耗尽时返回None
+    pub fn new() -> Option<Self> {
+        let frame = frame_alloc()?;
+        Some(PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
-        }
+        })
     }
 }
 
@@ -100,10 +110,15 @@ impl PageTable {
     /// 建立映射，在所有操作后，再刷新tlb，映射不做刷新，避免耗费不必要的开销
     /// 即找到结点，并完善pte = ppn + flags + rsw
     /// 等于强行修改ppn了
-    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
+    /// 申请不到中间节点的物理页时返回false
+    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) -> bool {
         // 初始化结点pte
-        if let Some(pte) = self.find_pte_crate(vpn) {
-            *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        match self.find_pte_crate(vpn) {
+            Some(pte) => {
+                *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+                true
+            }
+            None => false,
         }
     }
 
@@ -116,7 +131,7 @@ impl PageTable {
         }
     }
 
-    /// 从根节点向下寻找所有节点，如无则创建一块物理页ppn，最后一级将返回结点 pte
+    /// 从根节点向下寻找所有节点，如无则创建一块物理页ppn，最后一级将返回结点 pte，物理页耗尽时返回None
     /// 图示：http://rcore-os.cn/rCore-Tutorial-Book-v3/_images/sv39-full.png
     fn find_pte_crate(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
         let idxs = vpn.indexes();
@@ -130,7 +145,7 @@ impl PageTable {
             // step2：如果pte不存在，则申请一个ppn，再等下一次循环的时候，把pte
             if !pte.is_valid() {
                 // 申请一个物理页ppn
-                let frame = frame_alloc().unwrap();
+                let frame = frame_alloc()?;
 
                 // pte 中 存入一个 ppn
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
@@ -202,16 +217,50 @@ impl PageTable {
     }
 }
 
-/// translate a pointer to a mutable u8 Vec through page table
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+/// 懒分配的页面在内核第一次访问时可能还没有映射，写时复制的页面在内核写入前也需要先复制，
+/// 这两种情况都先替当前任务处理这次缺页
+///
+/// 地址不属于用户（包括跳板和Trap上下文）或者不允许`access`时返回None
+fn translate_user_vpn(page_table: &PageTable, vpn: VirtPageNum, access: MapPermission) -> Option<PhysPageNum> {
+    if let Some(pte) = page_table.translate(vpn) {
+        if !pte.is_user() || !pte.readable() {
+            return None;
+        }
+        if !access.contains(MapPermission::W) || pte.writable() {
+            return Some(pte.ppn());
+        }
+    }
+    let va: VirtAddr = vpn.into();
+    if !handle_page_fault(va.into(), access) {
+        return None;
+    }
+    page_table.translate(vpn).map(|pte| pte.ppn())
+}
+
+/// translate a pointer to a mutable u8 Vec through page table, None if any page is not accessible
+pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Option<Vec<&'static mut [u8]>> {
+    translate_byte_buffer(token, ptr, len, MapPermission::R)
+}
+
+/// 和translated_byte_buffer一样，但内核会写入这段缓冲区，写时复制的页面需要先复制
+pub fn translated_byte_buffer_mut(token: usize, ptr: *mut u8, len: usize) -> Option<Vec<&'static mut [u8]>> {
+    translate_byte_buffer(token, ptr, len, MapPermission::W)
+}
+
+fn translate_byte_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+    access: MapPermission,
+) -> Option<Vec<&'static mut [u8]>> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
-    let end = start + len;
+    let end = start.checked_add(len)?;
     let mut v = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
-        let ppn = page_table.translate(vpn).unwrap().ppn();
+        let ppn = translate_user_vpn(&page_table, vpn, access)?;
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
         end_va = end_va.min(VirtAddr::from(end));
@@ -222,5 +271,75 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
         }
         start = end_va.into();
     }
-    v
+    Some(v)
+}
+
+/// 用户空间中的一段缓冲区，按物理页切成了若干段
+pub struct UserBuffer {
+    pub buffers: Vec<&'static mut [u8]>,
+}
+
+impl UserBuffer {
+    pub fn new(buffers: Vec<&'static mut [u8]>) -> Self {
+        Self { buffers }
+    }
+}
+
+/// 把`value`按字节复制到用户空间的`dst`处，`dst`可以跨越页面，地址无效时返回None
+pub fn copy_to_user<T>(token: usize, dst: *mut T, value: &T) -> Option<()> {
+    let src = unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    };
+    let mut copied = 0;
+    for buffer in translated_byte_buffer_mut(token, dst as *mut u8, src.len())? {
+        buffer.copy_from_slice(&src[copied..copied + buffer.len()]);
+        copied += buffer.len();
+    }
+    Some(())
+}
+
+/// 检查`va`处的`T`没有跨越页面并且按`T`对齐，这样才能在物理页中直接引用它
+fn fits_in_page<T>(va: VirtAddr) -> bool {
+    va.page_offset() + core::mem::size_of::<T>() <= PAGE_SIZE
+        && usize::from(va) % core::mem::align_of::<T>() == 0
+}
+
+/// 通过页表把用户空间的指针翻译成内核中的只读引用，地址无效、未对齐或者跨越页面时返回None
+pub fn translated_ref<T>(token: usize, ptr: *const T) -> Option<&'static T> {
+    let page_table = PageTable::from_token(token);
+    let va = VirtAddr::from(ptr as usize);
+    if !fits_in_page::<T>(va) {
+        return None;
+    }
+    let pa: PhysAddr = translate_user_vpn(&page_table, va.floor(), MapPermission::R)?.into();
+    unsafe { ((pa.0 + va.page_offset()) as *const T).as_ref() }
+}
+
+/// 通过页表把用户空间的指针翻译成内核中可以直接修改的引用，地址无效、未对齐或者跨越页面时返回None
+pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> Option<&'static mut T> {
+    let page_table = PageTable::from_token(token);
+    let va = VirtAddr::from(ptr as usize);
+    if !fits_in_page::<T>(va) {
+        return None;
+    }
+    let pa: PhysAddr = translate_user_vpn(&page_table, va.floor(), MapPermission::W)?.into();
+    unsafe { ((pa.0 + va.page_offset()) as *mut T).as_mut() }
+}
+
+/// 从用户空间读取一个以'\0'结尾的字符串，字符串中有无效的地址时返回None
+pub fn translated_str(token: usize, ptr: *const u8) -> Option<String> {
+    let page_table = PageTable::from_token(token);
+    let mut string = String::new();
+    let mut va = ptr as usize;
+    loop {
+        let vpn = VirtAddr::from(va).floor();
+        let pa: PhysAddr = translate_user_vpn(&page_table, vpn, MapPermission::R)?.into();
+        let ch = unsafe { *((pa.0 + VirtAddr::from(va).page_offset()) as *const u8) };
+        if ch == 0 {
+            break;
+        }
+        string.push(ch as char);
+        va = va.checked_add(1)?;
+    }
+    Some(string)
 }
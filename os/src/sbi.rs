@@ -4,8 +4,7 @@ use core::arch::asm;
 
 const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
-
-// const SBI_CONSOLE_GETCHAR: usize = 2;
+const SBI_CONSOLE_GETCHAR: usize = 2;
 // const SBI_CLEAR_IPI: usize = 3;
 // const SBI_SEND_IPI: usize = 4;
 // const SBI_REMOTE_FENCE_I: usize = 5;
@@ -37,9 +36,13 @@ pub fn console_putchar(c: usize) {
 }
 
 /// use sbi call to getchar from console (qemu uart handler)
-// pub fn console_getchar() -> usize {
-//     sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0)
-// }
+/// 没有输入时返回None，legacy接口此时返回-1；返回0表示读到了NUL字节
+pub fn console_getchar() -> Option<u8> {
+    match sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0) {
+        usize::MAX => None,
+        c => Some(c as u8),
+    }
+}
 use crate::board::QEMUExit;
 /// use sbi call to shutdown the kernel
 pub fn shutdown() -> ! {
@@ -1,22 +1,102 @@
 //! File and filesystem-related syscalls
+//!
+//! 所有读写都通过当前任务的文件描述符表分发到对应的[`File`](crate::fs::File)，
+//! 无效的文件描述符返回-1
 
-use crate::mm::translated_byte_buffer;
-use crate::task::current_user_token;
-
-const FD_STDOUT: usize = 1;
+use crate::fs::{make_pipe, open_file, unlink_file, OpenFlags, Stat};
+use crate::mm::{
+    copy_to_user, translated_byte_buffer, translated_byte_buffer_mut, translated_refmut,
+    translated_str, UserBuffer,
+};
+use crate::task::{add_current_file, close_current_file, current_file, current_user_token};
 
 /// write buf of length `len`  to a file with `fd`
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            let buffers = translated_byte_buffer(current_user_token(), buf, len);
-            for buffer in buffers {
-                print!("{}", core::str::from_utf8(buffer).unwrap());
+    match current_file(fd) {
+        Some(file) if file.writable() => {
+            match translated_byte_buffer(current_user_token(), buf, len) {
+                Some(buffers) => file
+                    .write(UserBuffer::new(buffers))
+                    .map_or(-1, |written| written as isize),
+                None => -1,
+            }
+        }
+        _ => -1,
+    }
+}
+
+/// read at most `len` bytes from a file with `fd` into buf
+///
+/// 标准输入没有输入时会阻塞，普通文件读到末尾时返回0
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    match current_file(fd) {
+        Some(file) if file.readable() => {
+            match translated_byte_buffer_mut(current_user_token(), buf, len) {
+                Some(buffers) => file.read(UserBuffer::new(buffers)) as isize,
+                None => -1,
             }
-            len as isize
         }
-        _ => {
-            panic!("Unsupported fd in sys_write!");
+        _ => -1,
+    }
+}
+
+/// 打开根目录下名为`path`的文件，`path`以'\0'结尾，成功时返回文件描述符，失败或者打开的文件太多时返回-1
+pub fn sys_open(path: *const u8, flags: u32) -> isize {
+    let path = match translated_str(current_user_token(), path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    OpenFlags::from_bits(flags)
+        .and_then(|flags| open_file(path.as_str(), flags))
+        .and_then(|inode| add_current_file(inode))
+        .map_or(-1, |fd| fd as isize)
+}
+
+/// 删除根目录下名为`path`的文件，`path`以'\0'结尾，文件不存在或者还被打开着时返回-1
+pub fn sys_unlinkat(path: *const u8) -> isize {
+    match translated_str(current_user_token(), path) {
+        Some(path) if unlink_file(path.as_str()) => 0,
+        _ => -1,
+    }
+}
+
+/// 关闭文件描述符`fd`，成功时返回0
+pub fn sys_close(fd: usize) -> isize {
+    if close_current_file(fd) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 把`fd`对应文件的状态写入`st`，成功时返回0
+pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
+    match current_file(fd) {
+        Some(file) => copy_to_user(current_user_token(), st, &file.stat()).map_or(-1, |_| 0),
+        None => -1,
+    }
+}
+
+/// 创建一个管道，把读端和写端的文件描述符依次写入`pipe`，成功时返回0，`pipe`无效或者打开的文件太多时返回-1
+pub fn sys_pipe(pipe: *mut [usize; 2]) -> isize {
+    // 先检查地址，避免分配了文件描述符却无法告诉应用
+    let pipe = match translated_refmut(current_user_token(), pipe) {
+        Some(pipe) => pipe,
+        None => return -1,
+    };
+    let (read_end, write_end) = make_pipe();
+    let read_fd = match add_current_file(read_end) {
+        Some(fd) => fd,
+        None => return -1,
+    };
+    match add_current_file(write_end) {
+        Some(write_fd) => {
+            *pipe = [read_fd, write_fd];
+            0
+        }
+        None => {
+            close_current_file(read_fd);
+            -1
         }
     }
 }
@@ -1,11 +1,32 @@
 //! Process management syscalls
-use crate::task::{change_program_brk, exit_current_and_run_next, suspend_current_and_run_next};
-use crate::timer::get_time_us;
+use crate::fs::read_app;
+use crate::config::{MAX_SYSCALL_NUM, PAGE_SIZE};
+use crate::mm::{
+    copy_to_user, translated_byte_buffer_mut, translated_ref, translated_refmut, translated_str,
+    MapPermission,
+};
+use core::mem::size_of;
+use crate::task::{
+    block_current_and_run_next, change_program_brk, current_cpu_time_us, current_pid,
+    current_user_token, exec_current,
+    exit_current_and_run_next, fork_current, mmap_current, munmap_current, scheduler_level, set_current_priority,
+    suspend_current_and_run_next, waitpid_current, with_task_stats,
+};
+use crate::timer::{add_timer, get_time_ns, get_time_us, realtime_ns, TimeSpec, TimeVal};
+
+/// Unix时间
+const CLOCK_REALTIME: usize = 0;
+/// 开机以来的时间，不会被调整
+const CLOCK_MONOTONIC: usize = 1;
+/// 当前进程用掉的cpu时间
+const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+/// 每个进程只有一个线程，和CLOCK_PROCESS_CPUTIME_ID相同
+const CLOCK_THREAD_CPUTIME_ID: usize = 3;
 
 /// 退出应用，并进行下一个应用
 pub fn sys_exit(exit_code: i32) -> ! {
     println!("[kernel] Application exited with code {}", exit_code);
-    exit_current_and_run_next();
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
@@ -15,9 +36,102 @@ pub fn sys_yield() -> isize {
     0
 }
 
-/// milliseconds 时间
-pub fn sys_get_time() -> isize {
-    get_time_us() as isize
+/// 把当前的Unix时间写到`ts`，不支持时区，`_tz`被忽略，`ts`无效时返回-1
+pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
+    copy_to_user(current_user_token(), ts, &TimeVal::from_ns(realtime_ns())).map_or(-1, |_| 0)
+}
+
+/// 把`clock_id`对应的时钟写到`tp`，不支持的时钟或者`tp`无效时返回-1
+pub fn sys_clock_gettime(clock_id: usize, tp: *mut TimeSpec) -> isize {
+    let ns = match clock_id {
+        CLOCK_REALTIME => realtime_ns(),
+        CLOCK_MONOTONIC => get_time_ns(),
+        CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => current_cpu_time_us() * 1000,
+        _ => return -1,
+    };
+    copy_to_user(current_user_token(), tp, &TimeSpec::from_ns(ns)).map_or(-1, |_| 0)
+}
+
+/// 让当前任务睡眠至少`us`微秒，期间不占用cpu
+///
+/// 时间都按饱和运算处理，太大的值相当于一直睡眠，而不是回绕成已经过去的时刻
+fn sleep_us(us: usize) {
+    if us == 0 {
+        suspend_current_and_run_next();
+        return;
+    }
+    add_timer(get_time_us().saturating_add(us), current_pid());
+    block_current_and_run_next();
+}
+
+/// 睡眠`ms`毫秒
+pub fn sys_sleep(ms: usize) -> isize {
+    sleep_us(ms.saturating_mul(1000));
+    0
+}
+
+/// 按`req`睡眠，不会被信号打断，所以`rem`总是被写为0，`req`或者`rem`无效时返回-1
+pub fn sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec) -> isize {
+    let token = current_user_token();
+    let req = match translated_ref(token, req) {
+        Some(req) => *req,
+        None => return -1,
+    };
+    if req.nsec >= 1_000_000_000 {
+        return -1;
+    }
+    // 不足1微秒的部分向上取整
+    sleep_us(req.sec.saturating_mul(1_000_000).saturating_add((req.nsec + 999) / 1000));
+    if !rem.is_null() {
+        return copy_to_user(current_user_token(), rem, &TimeSpec::default()).map_or(-1, |_| 0);
+    }
+    0
+}
+
+/// sys_task_info返回给应用的统计信息，时间的单位都是微秒
+#[repr(C)]
+pub struct TaskInfo {
+    /// 第一次被调度的时间，还没有被调度过时为0
+    pub first_time: usize,
+    /// 在用户态运行的时间
+    pub user_time: usize,
+    /// 在内核态运行的时间
+    pub kernel_time: usize,
+    /// 被切换进来的次数
+    pub switch_count: usize,
+    /// 每个系统调用号被调用的次数
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
+}
+
+/// 把一段数据看作字节
+fn as_bytes<T>(slice: &[T]) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(slice.as_ptr() as *const u8, core::mem::size_of_val(slice)) }
+}
+
+/// 把`pid`的统计信息写到`info`，任务不存在或者`info`无效时返回-1
+///
+/// TaskInfo有2KB多，不在8KB的内核栈上构造：先翻译`info`并处理好缺页，
+/// 再在借用统计信息的同时按TaskInfo的字段顺序直接写进用户空间
+pub fn sys_task_info(pid: usize, info: *mut TaskInfo) -> isize {
+    let token = current_user_token();
+    let buffers = match translated_byte_buffer_mut(token, info as *mut u8, size_of::<TaskInfo>()) {
+        Some(buffers) => buffers,
+        None => return -1,
+    };
+    with_task_stats(pid, |stats| {
+        let header = [
+            stats.first_time.unwrap_or(0),
+            stats.user_time,
+            stats.kernel_time,
+            stats.switch_count,
+        ];
+        let src = as_bytes(&header).iter().chain(as_bytes(&stats.syscall_times));
+        let dst = buffers.into_iter().flat_map(|buffer| buffer.iter_mut());
+        for (dst, src) in dst.zip(src) {
+            *dst = *src;
+        }
+    })
+    .map_or(-1, |_| 0)
 }
 
 /// change data segment size
@@ -28,3 +142,96 @@ pub fn sys_sbrk(size: i32) -> isize {
         -1
     }
 }
+
+/// 把[start, start + len)映射进当前任务的地址空间，`prot`的第0/1/2位分别表示可读/可写/可执行
+///
+/// start需要按页对齐，prot不能为0或包含其它位，也不能只写不读（RISC-V保留了这种页表项），
+/// 范围不能和已有的映射重叠，否则返回-1
+pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
+    if start % PAGE_SIZE != 0 || len == 0 || prot & !0x7 != 0 || prot & 0x7 == 0 {
+        return -1;
+    }
+    if prot & 0x3 == 0x2 {
+        return -1;
+    }
+    if start.checked_add(len).is_none() {
+        return -1;
+    }
+    // prot的R/W/X比MapPermission低一位
+    let perm = MapPermission::from_bits((prot << 1) as u8).unwrap() | MapPermission::U;
+    if mmap_current(start, len, perm) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 解除[start, start + len)的映射，范围内有未映射的页面或者和堆重叠时返回-1
+pub fn sys_munmap(start: usize, len: usize) -> isize {
+    if start % PAGE_SIZE != 0 || len == 0 || start.checked_add(len).is_none() {
+        return -1;
+    }
+    if munmap_current(start, len) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// 设置当前任务的调度优先级，`prio`至少为2，成功时返回`prio`，否则返回-1
+pub fn sys_set_priority(prio: isize) -> isize {
+    if prio < 2 {
+        return -1;
+    }
+    set_current_priority(prio as usize);
+    prio
+}
+
+/// 诊断用：任务`pid`在多级反馈队列中的层级，第0层优先级最高
+///
+/// 任务不存在或者当前调度策略没有层级时返回-1
+pub fn sys_sched_level(pid: usize) -> isize {
+    scheduler_level(pid).map_or(-1, |level| level as isize)
+}
+
+/// 当前任务的pid
+pub fn sys_getpid() -> isize {
+    current_pid() as isize
+}
+
+/// 复制当前任务，父任务返回子任务的pid，子任务返回0，pid或物理页用完时返回-1
+pub fn sys_fork() -> isize {
+    fork_current().map_or(-1, |pid| pid as isize)
+}
+
+/// 用名为`path`的应用替换当前任务的地址空间，`path`以'\0'结尾
+///
+/// `path`无效、找不到应用或者文件不是合法的RISC-V ELF时返回-1
+pub fn sys_exec(path: *const u8) -> isize {
+    let path = match translated_str(current_user_token(), path) {
+        Some(path) => path,
+        None => return -1,
+    };
+    match read_app(path.as_str()) {
+        Some(data) if exec_current(&data) => 0,
+        _ => -1,
+    }
+}
+
+/// 等待子任务退出并把退出码写入`exit_code_ptr`，`pid`为-1时表示任意子任务
+///
+/// 没有符合条件的子任务或者`exit_code_ptr`无效时返回-1，子任务仍在运行返回-2
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+    // 先检查地址，避免子任务被回收后退出码无处可写
+    let exit_code_ref = match translated_refmut(current_user_token(), exit_code_ptr) {
+        Some(exit_code_ref) => exit_code_ref,
+        None => return -1,
+    };
+    match waitpid_current(pid) {
+        Ok((found_pid, exit_code)) => {
+            *exit_code_ref = exit_code;
+            found_pid as isize
+        }
+        Err(err) => err,
+    }
+}
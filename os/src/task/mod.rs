@@ -8,21 +8,36 @@
 //!
 //! Be careful when you see `__switch` ASM function in `switch.S`. Control flow around this function
 //! might not be what you expect.
+//!
+//! 任务之间不直接切换：让出cpu的任务先切换到空闲控制流（[`run_tasks`]中的循环，运行在启动栈上），
+//! 由它选出下一个任务；没有Ready的任务时它打开中断并wfi，直到有任务被唤醒。
 
 mod context;
+mod pid;
+mod scheduler;
 mod switch;
 
 #[allow(clippy::module_inception)]
 mod task;
 
-use crate::loader::{get_app_data, get_num_app};
+use crate::fs::{read_app, File};
+use crate::mm::{MapPermission, VirtAddr};
 use crate::sync::UPSafeCell;
+use crate::timer::{get_time_us, remove_timer};
+use crate::trap::wait_for_interrupt;
+use crate::trap::TrapContext;
 use lazy_static::*;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
+use scheduler::{DefaultScheduler, Scheduler};
 use switch::__switch;
 use task::{TaskControlBlock, TaskStatus};
 
 pub use context::TaskContext;
+pub use task::TaskStats;
+
+/// initproc是第一个被创建的任务，孤儿任务都会交给它回收
+const INITPROC_PID: usize = 0;
 
 /// The task manager, where all the tasks are managed.
 ///
@@ -34,8 +49,6 @@ pub use context::TaskContext;
 /// borrowing checks to runtime. You can see examples on how to use `inner` in
 /// existing functions on `TaskManager`.
 pub struct TaskManager {
-    /// 总应用数
-    num_app: usize,
     /// 管理所有任务块，记录当前运行的应用id
     /// 任何对于 static mut 变量的访问控制都是 unsafe 的，而我们要在编程中尽量避免使用 unsafe ，这样才能让编译器负责更多的安全性检查。
     inner: UPSafeCell<TaskManagerInner>,
@@ -43,11 +56,17 @@ pub struct TaskManager {
 
 /// 需要设置一个inner是因为
 pub struct TaskManagerInner {
-    /// 任务块
+    /// 任务块，fork会往里面追加，waitpid回收后会被移除
     tasks: Vec<TaskControlBlock>,
     // tasks: [TaskControlBlock; MAX_APP_NUM],
     /// id of current `Running` task
     current_task: usize,
+    /// 决定下一个运行哪个Ready任务，只保存pid
+    scheduler: DefaultScheduler,
+    /// 空闲控制流的上下文，任务让出cpu时切换到这里
+    idle_task_cx: TaskContext,
+    /// 是否有任务失败，决定退出QEMU时的返回码
+    failed: bool,
 }
 
 // lazy_static! {
@@ -79,20 +98,24 @@ pub struct TaskManagerInner {
 
 lazy_static! {
     /// a `TaskManager` global instance through lazy_static!
+    ///
+    /// 启动时只创建initproc，其余应用由它（或者它启动的shell）按名字加载
     pub static ref TASK_MANAGER: TaskManager = {
         println!("init TASK_MANAGER");
-        let num_app = get_num_app();
-        println!("num_app = {}", num_app);
-        let mut tasks: Vec<TaskControlBlock> = Vec::new();
-        for i in 0..num_app {
-            tasks.push(TaskControlBlock::new(get_app_data(i), i));
-        }
+        let initproc = TaskControlBlock::new(&read_app("initproc").expect("initproc not found"))
+            .expect("initproc is not a valid ELF");
+        assert_eq!(initproc.getpid(), INITPROC_PID);
+        let tasks: Vec<TaskControlBlock> = alloc::vec![initproc];
+        let mut scheduler = DefaultScheduler::new();
+        scheduler.add(INITPROC_PID);
         TaskManager {
-            num_app,
             inner: unsafe {
                 UPSafeCell::new(TaskManagerInner {
                     tasks,
                     current_task: 0,
+                    scheduler,
+                    idle_task_cx: TaskContext::zero_init(),
+                    failed: false,
                 })
             },
         }
@@ -100,26 +123,44 @@ lazy_static! {
 }
 
 impl TaskManager {
-    fn run_first_task(&self) -> ! {
-        let mut inner = self.inner.exclusive_access();
-        let task0 = &mut inner.tasks[0];
-        task0.task_status = TaskStatus::Running;
-
-        // ra: trap_return
-        let next_task_cx_ptr = &task0.task_cx as *const TaskContext;
+    /// 空闲控制流：不断选出下一个任务并切换过去，任务让出cpu后回到这里
+    ///
+    /// 没有Ready的任务时等待中断，所有任务都退出后关闭QEMU
+    fn run_tasks(&self) -> ! {
+        loop {
+            if let Some(next) = self.find_next_task() {
+                let mut inner = self.inner.exclusive_access();
+                inner.tasks[next].task_status = TaskStatus::Running;
+                inner.tasks[next].stats.on_schedule(get_time_us());
+                inner.current_task = next;
+                let idle_task_cx_ptr = &mut inner.idle_task_cx as *mut TaskContext;
+                // ra: trap_return
+                let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
 
-        // 如果不手动 drop 的话，编译器会在 __switch 返回时，也就是当前应用被切换回来的时候才 drop，
-        // 这期间都不能修改 TaskManagerInner ，甚至不能读（因为之前是可变借用），会导致内核 panic 报错退出
-        drop(inner);
-
-        // 充当填充参数
-        // todo 可以传递多一个参数去asm判断
-        let mut _unused = TaskContext::zero_init();
+                // 如果不手动 drop 的话，编译器会在 __switch 返回时，也就是当前应用被切换回来的时候才 drop，
+                // 这期间都不能修改 TaskManagerInner ，甚至不能读（因为之前是可变借用），会导致内核 panic 报错退出
+                drop(inner);
+                unsafe {
+                    __switch(idle_task_cx_ptr, next_task_cx_ptr);
+                }
+            } else if self.has_live_task() {
+                // 唤醒任务的时钟中断在trap_from_kernel中处理
+                wait_for_interrupt();
+            } else {
+                self.shutdown();
+            }
+        }
+    }
 
-        unsafe {
-            __switch(&mut _unused as *mut TaskContext, next_task_cx_ptr);
+    /// 所有任务都已经退出，按是否有任务失败设置QEMU的返回码
+    fn shutdown(&self) -> ! {
+        use crate::board::QEMUExit;
+        if self.inner.exclusive_access().failed {
+            println!("[kernel] Some applications failed!");
+            crate::board::QEMU_EXIT_HANDLE.exit_failure();
         }
-        panic!("unreachable in run_first_task!");
+        println!("All applications completed!");
+        crate::board::QEMU_EXIT_HANDLE.exit_success();
     }
 
     // todo
@@ -129,44 +170,146 @@ impl TaskManager {
         inner.tasks[inner.current_task].get_user_token()
     }
 
-    /// 将当前任务状态标记为TaskStatus::Ready
+    /// Get the current 'Running' task's trap context.
+    fn get_current_trap_cx(&self) -> &'static mut TrapContext {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].get_trap_cx()
+    }
+
+    /// Get the current 'Running' task's pid.
+    fn get_current_pid(&self) -> usize {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].getpid()
+    }
+
+    /// 将当前任务状态标记为TaskStatus::Ready，并交给调度器
     fn mark_current_suspended(&self) {
         let mut inner = self.inner.exclusive_access();
         let current = inner.current_task;
         inner.tasks[current].task_status = TaskStatus::Ready;
+        let pid = inner.tasks[current].getpid();
+        inner.scheduler.add(pid);
     }
 
-    /// 将当前任务状态标记为TaskStatus::Exited
-    fn mark_current_exited(&self) {
+    /// 将当前任务状态标记为TaskStatus::Blocked，被唤醒之前不会交给调度器
+    fn mark_current_blocked(&self) {
         let mut inner = self.inner.exclusive_access();
         let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Exited;
+        inner.tasks[current].task_status = TaskStatus::Blocked;
     }
 
-    /// 寻找为Ready的应用
+    /// 把处于Blocked状态的`pid`重新交给调度器，其他状态的任务不受影响
+    fn wakeup_task(&self, pid: usize) {
+        let mut inner = self.inner.exclusive_access();
+        if let Some(task) = inner
+            .tasks
+            .iter_mut()
+            .find(|task| task.getpid() == pid && task.task_status == TaskStatus::Blocked)
+        {
+            task.task_status = TaskStatus::Ready;
+            inner.scheduler.add(pid);
+        }
+    }
+
+    /// 是否还有没有退出的任务
+    fn has_live_task(&self) -> bool {
+        let inner = self.inner.exclusive_access();
+        inner
+            .tasks
+            .iter()
+            .any(|task| task.task_status != TaskStatus::Exited)
+    }
+
+    /// 将当前任务状态标记为TaskStatus::Exited，记录退出码并回收用户空间的物理页
+    ///
+    /// 退出码由父进程检查，只有直接由initproc负责的任务（shell和孤儿进程）以及initproc自己
+    /// 以非0退出码结束时才算作失败。测试中被故意杀死的子进程不算，
+    /// shell运行的应用失败时，shell自己以非0退出码结束，由此计入
+    fn mark_current_exited(&self, exit_code: i32) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let pid = inner.tasks[current].getpid();
+        let parent = inner.tasks[current].parent;
+        if exit_code != 0 && parent.map_or(true, |parent| parent == INITPROC_PID) {
+            inner.failed = true;
+        }
+        let task = &mut inner.tasks[current];
+        task.task_status = TaskStatus::Exited;
+        task.exit_code = exit_code;
+        // 页表在任务被回收时才释放，这里先释放数据页
+        task.memory_set.recycle_data_pages();
+        let files = core::mem::take(&mut task.fd_table);
+        inner.scheduler.remove(pid);
+        remove_timer(pid);
+        // 子任务交给initproc回收
+        for task in inner.tasks.iter_mut().filter(|task| task.parent == Some(pid)) {
+            task.parent = Some(INITPROC_PID);
+        }
+        // 关闭所有打开的文件，文件的析构可能需要访问TASK_MANAGER，放在释放inner之后
+        drop(inner);
+        drop(files);
+    }
+
+    /// 由调度器选出下一个Ready的任务，返回它在tasks中的下标
     fn find_next_task(&self) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let pid = inner.scheduler.fetch()?;
+        let idx = inner.tasks.iter().position(|task| task.getpid() == pid);
+        assert!(
+            idx.map_or(false, |idx| inner.tasks[idx].task_status == TaskStatus::Ready),
+            "scheduler returned pid {} which is not ready",
+            pid
+        );
+        idx
+    }
+
+    /// 当前任务经过了一个时钟周期，返回是否需要抢占
+    fn tick_current(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let pid = inner.tasks[current].getpid();
+        inner.scheduler.on_tick(pid)
+    }
+
+    /// `pid`在调度器中的队列层级，任务不存在、已经退出或者调度策略没有层级时返回None
+    fn scheduler_level(&self, pid: usize) -> Option<usize> {
         let inner = self.inner.exclusive_access();
+        inner
+            .tasks
+            .iter()
+            .find(|task| task.getpid() == pid && task.task_status != TaskStatus::Exited)?;
+        inner.scheduler.level(pid)
+    }
+
+    /// 修改当前任务的统计信息
+    fn update_current_stats(&self, f: impl FnOnce(&mut TaskStats)) {
+        let mut inner = self.inner.exclusive_access();
         let current = inner.current_task;
+        f(&mut inner.tasks[current].stats);
+    }
 
-        // 因为不会包括最后一位数，需要+1
-        (current + 1..current + self.num_app + 1)
-            // 取余 循环一圈 1 -> 2 -> 0
-            .map(|id| {
-                println!("raw id {}", id);
-                id % self.num_app
-            })
-            .find(|id| {
-                let status = inner.tasks[*id].task_status;
-                println!(
-                    "running app id {} status: {:?} app_num: {} start: {} end: {}",
-                    id,
-                    status,
-                    self.num_app,
-                    current + 1,
-                    current + self.num_app
-                );
-                status == TaskStatus::Ready
-            })
+    /// 当前任务一共用掉的cpu时间（微秒）
+    fn current_cpu_time_us(&self) -> usize {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task].stats.cpu_time(get_time_us())
+    }
+
+    /// 借用`pid`的统计信息执行`f`，任务不存在时返回None；已经退出但还没有被回收的任务也可以查询
+    fn with_task_stats<R>(&self, pid: usize, f: impl FnOnce(&TaskStats) -> R) -> Option<R> {
+        let inner = self.inner.exclusive_access();
+        inner
+            .tasks
+            .iter()
+            .find(|task| task.getpid() == pid)
+            .map(|task| f(&task.stats))
+    }
+
+    /// 设置当前任务的优先级
+    fn set_current_priority(&self, priority: usize) {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let pid = inner.tasks[current].getpid();
+        inner.scheduler.set_priority(pid, priority);
     }
 
     /// Change the current 'Running' task's program break
@@ -176,33 +319,124 @@ impl TaskManager {
         inner.tasks[cur].change_program_brk(size)
     }
 
-    /// Switch current `Running` task to the task we have found,
-    /// or there is no `Ready` task and we can exit with all applications completed
+    /// 在当前任务的地址空间中插入[start, start + len)的懒加载逻辑段
+    fn mmap_current(&self, start: usize, len: usize, perm: MapPermission) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        inner.tasks[cur]
+            .memory_set
+            .insert_lazy_area(VirtAddr(start), VirtAddr(start + len), perm)
+    }
+
+    /// 解除当前任务地址空间中[start, start + len)的映射
+    fn munmap_current(&self, start: usize, len: usize) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let cur = inner.current_task;
+        inner.tasks[cur].munmap(VirtAddr(start), VirtAddr(start + len))
+    }
+
+    /// 复制当前任务，返回子任务的pid，pid或物理页用完时返回None
+    fn fork_current(&self) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let child = inner.tasks[current].fork()?;
+        let pid = child.getpid();
+        // 子任务从fork返回0
+        child.get_trap_cx().x[10] = 0;
+        inner.tasks.push(child);
+        inner.scheduler.add(pid);
+        Some(pid)
+    }
+
+    /// 用`elf_data`替换当前任务的地址空间，ELF无效时返回false
+    fn exec_current(&self, elf_data: &[u8]) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current].exec(elf_data)
+    }
+
+    /// 当前任务的文件描述符`fd`对应的文件
+    fn current_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        let inner = self.inner.exclusive_access();
+        inner.tasks[inner.current_task]
+            .fd_table
+            .get(fd)
+            .cloned()
+            .flatten()
+    }
+
+    /// 把`file`放入当前任务的文件描述符表，返回分配的文件描述符，表满时返回None
+    fn add_current_file(&self, file: Arc<dyn File>) -> Option<usize> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let task = &mut inner.tasks[current];
+        let fd = task.alloc_fd()?;
+        task.fd_table[fd] = Some(file);
+        Some(fd)
+    }
+
+    /// 从当前任务的文件描述符表中取出`fd`，`fd`无效时返回None
+    fn take_current_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current].fd_table.get_mut(fd)?.take()
+    }
+
+    /// 在当前任务的地址空间中处理缺页异常
+    fn handle_current_page_fault(&self, va: usize, access: MapPermission) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current]
+            .memory_set
+            .handle_page_fault(va.into(), access)
+    }
+
+    /// 回收当前任务的一个已退出的子任务，`pid`为-1时表示任意子任务
+    ///
+    /// 返回-1表示没有符合条件的子任务，返回-2表示子任务仍未退出
+    fn waitpid_current(&self, pid: isize) -> Result<(usize, i32), isize> {
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        let current_pid = inner.tasks[current].getpid();
+        let is_target = |task: &TaskControlBlock| {
+            task.parent == Some(current_pid) && (pid == -1 || pid as usize == task.getpid())
+        };
+        if !inner.tasks.iter().any(is_target) {
+            return Err(-1);
+        }
+        let idx = inner
+            .tasks
+            .iter()
+            .position(|task| is_target(task) && task.task_status == TaskStatus::Exited)
+            .ok_or(-2isize)?;
+        let child = inner.tasks.remove(idx);
+        // 移除的任务在当前任务之前，当前任务的下标需要前移
+        if idx < current {
+            inner.current_task -= 1;
+        }
+        // child在这里被drop，pid、内核栈和页表随之回收
+        Ok((child.getpid(), child.exit_code))
+    }
+
+    /// Switch current task to the idle control flow, which picks the next task to run
     fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
-            let mut inner = self.inner.exclusive_access();
-            let current = inner.current_task;
-            inner.tasks[next].task_status = TaskStatus::Running;
-            inner.current_task = next;
-            let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
-            let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
-            drop(inner);
-            // before this, we should drop local variables that must be dropped manually
-            unsafe {
-                __switch(current_task_cx_ptr, next_task_cx_ptr);
-            }
-            // go back to user mode
-        } else {
-            println!("All applications completed!");
-            use crate::board::QEMUExit;
-            crate::board::QEMU_EXIT_HANDLE.exit_success();
+        let mut inner = self.inner.exclusive_access();
+        let current = inner.current_task;
+        inner.tasks[current].stats.charge_kernel(get_time_us());
+        let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
+        let idle_task_cx_ptr = &inner.idle_task_cx as *const TaskContext;
+        drop(inner);
+        // before this, we should drop local variables that must be dropped manually
+        unsafe {
+            __switch(current_task_cx_ptr, idle_task_cx_ptr);
         }
+        // go back to user mode
     }
 }
 
-/// run first task
-pub fn run_first_task() {
-    TASK_MANAGER.run_first_task();
+/// run tasks until all of them have exited, never returns
+pub fn run_tasks() -> ! {
+    TASK_MANAGER.run_tasks();
 }
 
 /// rust next task
@@ -216,8 +450,8 @@ fn mark_current_suspended() {
 }
 
 /// exit current task
-fn mark_current_exited() {
-    TASK_MANAGER.mark_current_exited();
+fn mark_current_exited(exit_code: i32) {
+    TASK_MANAGER.mark_current_exited(exit_code);
 }
 
 /// suspend current task, then run next task
@@ -226,9 +460,64 @@ pub fn suspend_current_and_run_next() {
     run_next_task();
 }
 
+/// called on every timer interrupt, switch to another task if the scheduler asks to
+pub fn on_timer_tick() {
+    if TASK_MANAGER.tick_current() {
+        suspend_current_and_run_next();
+    }
+}
+
+/// scheduler queue level of task `pid`, for diagnostics
+pub fn scheduler_level(pid: usize) -> Option<usize> {
+    TASK_MANAGER.scheduler_level(pid)
+}
+
+/// charge the time since the last checkpoint to user mode, called when trapping into the kernel
+pub fn account_trap_enter() {
+    TASK_MANAGER.update_current_stats(|stats| stats.charge_user(get_time_us()));
+}
+
+/// charge the time since the last checkpoint to kernel mode, called before returning to user mode
+pub fn account_trap_return() {
+    TASK_MANAGER.update_current_stats(|stats| stats.charge_kernel(get_time_us()));
+}
+
+/// count one call of `syscall_id` for current task
+pub fn record_syscall(syscall_id: usize) {
+    TASK_MANAGER.update_current_stats(|stats| stats.record_syscall(syscall_id));
+}
+
+/// CPU time used by current task so far, in microseconds
+pub fn current_cpu_time_us() -> usize {
+    TASK_MANAGER.current_cpu_time_us()
+}
+
+/// run `f` on the accounting of task `pid`, None if there is no such task
+///
+/// `f`运行时TASK_MANAGER处于借用状态，不能再访问它，也不能触发用户空间的缺页
+pub fn with_task_stats<R>(pid: usize, f: impl FnOnce(&TaskStats) -> R) -> Option<R> {
+    TASK_MANAGER.with_task_stats(pid, f)
+}
+
+/// set the scheduling priority of current task
+pub fn set_current_priority(priority: usize) {
+    TASK_MANAGER.set_current_priority(priority);
+}
+
+/// block current task until someone calls [`wakeup_task`], then run next task
+pub fn block_current_and_run_next() {
+    TASK_MANAGER.mark_current_blocked();
+    run_next_task();
+}
+
+/// make the blocked task `pid` ready again
+pub fn wakeup_task(pid: usize) {
+    TASK_MANAGER.wakeup_task(pid);
+}
+
 /// exit current task,  then run next task
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
+pub fn exit_current_and_run_next(exit_code: i32) {
+    mark_current_exited(exit_code);
     run_next_task();
 }
 
@@ -237,6 +526,62 @@ pub fn current_user_token() -> usize {
     TASK_MANAGER.get_current_token()
 }
 
+/// Get the current 'Running' task's trap context.
+pub fn current_trap_cx() -> &'static mut TrapContext {
+    TASK_MANAGER.get_current_trap_cx()
+}
+
+/// Get the current 'Running' task's pid.
+pub fn current_pid() -> usize {
+    TASK_MANAGER.get_current_pid()
+}
+
+/// map [start, start + len) into current task's address space with `perm`
+pub fn mmap_current(start: usize, len: usize, perm: MapPermission) -> bool {
+    TASK_MANAGER.mmap_current(start, len, perm)
+}
+
+/// unmap [start, start + len) from current task's address space
+pub fn munmap_current(start: usize, len: usize) -> bool {
+    TASK_MANAGER.munmap_current(start, len)
+}
+
+/// fork current task, return the pid of the child, or None if no pid or memory is left
+pub fn fork_current() -> Option<usize> {
+    TASK_MANAGER.fork_current()
+}
+
+/// replace the address space of current task with `elf_data`, false if it is not a valid ELF
+pub fn exec_current(elf_data: &[u8]) -> bool {
+    TASK_MANAGER.exec_current(elf_data)
+}
+
+/// 当前任务的文件描述符`fd`对应的文件，`fd`无效时返回None
+pub fn current_file(fd: usize) -> Option<Arc<dyn File>> {
+    TASK_MANAGER.current_file(fd)
+}
+
+/// 把`file`放入当前任务的文件描述符表，返回分配的文件描述符，表满时返回None
+pub fn add_current_file(file: Arc<dyn File>) -> Option<usize> {
+    TASK_MANAGER.add_current_file(file)
+}
+
+/// 关闭当前任务的文件描述符`fd`，`fd`无效时返回false
+pub fn close_current_file(fd: usize) -> bool {
+    // 文件在TASK_MANAGER释放之后才被drop
+    TASK_MANAGER.take_current_file(fd).is_some()
+}
+
+/// resolve a page fault at `va` in current task's address space, return false if the access is illegal or memory runs out
+pub fn handle_page_fault(va: usize, access: MapPermission) -> bool {
+    TASK_MANAGER.handle_current_page_fault(va, access)
+}
+
+/// reap an exited child of current task, return its pid and exit code
+pub fn waitpid_current(pid: isize) -> Result<(usize, i32), isize> {
+    TASK_MANAGER.waitpid_current(pid)
+}
+
 /// Change the current 'Running' task's program break
 pub fn change_program_brk(size: i32) -> Option<usize> {
     TASK_MANAGER.change_current_program_brk(size)
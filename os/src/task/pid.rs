@@ -0,0 +1,104 @@
+//! Allocators for pid and kernel stack
+//!
+//! 每个任务拥有一个[`PidHandle`]和一个[`KernelStack`]，两者都在被drop时自动回收，
+//! 这样任务可以被无限次地创建和销毁。
+
+use crate::config::{kernel_stack_position, MAX_PID};
+use crate::mm::{MapPermission, VirtAddr, KERNEL_SPACE};
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// 和StackFrameAllocator一样的思路：[current, MAX_PID)从未分配过，recycled保存被回收的pid
+pub struct PidAllocator {
+    current: usize,
+    recycled: Vec<usize>,
+}
+
+impl PidAllocator {
+    pub fn new() -> Self {
+        Self {
+            current: 0,
+            recycled: Vec::new(),
+        }
+    }
+
+    /// 所有pid都在使用中时返回None
+    pub fn alloc(&mut self) -> Option<PidHandle> {
+        if let Some(pid) = self.recycled.pop() {
+            Some(PidHandle(pid))
+        } else if self.current < MAX_PID {
+            self.current += 1;
+            Some(PidHandle(self.current - 1))
+        } else {
+            None
+        }
+    }
+
+    pub fn dealloc(&mut self, pid: usize) {
+        assert!(pid < self.current);
+        // 检测是否已经被回收过
+        assert!(
+            !self.recycled.iter().any(|ppid| *ppid == pid),
+            "pid {} has been deallocated!",
+            pid
+        );
+        self.recycled.push(pid);
+    }
+}
+
+lazy_static! {
+    static ref PID_ALLOCATOR: UPSafeCell<PidAllocator> =
+        unsafe { UPSafeCell::new(PidAllocator::new()) };
+}
+
+/// pid的所有权，drop后pid被回收
+pub struct PidHandle(pub usize);
+
+impl Drop for PidHandle {
+    fn drop(&mut self) {
+        PID_ALLOCATOR.exclusive_access().dealloc(self.0);
+    }
+}
+
+/// 申请一个pid，pid用完时返回None
+pub fn pid_alloc() -> Option<PidHandle> {
+    PID_ALLOCATOR.exclusive_access().alloc()
+}
+
+/// 内核栈，位置由pid决定，创建时映射进KERNEL_SPACE，drop时解除映射并回收物理页
+pub struct KernelStack {
+    pid: usize,
+}
+
+impl KernelStack {
+    /// 物理页耗尽时返回None
+    pub fn new(pid_handle: &PidHandle) -> Option<Self> {
+        let pid = pid_handle.0;
+        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(pid);
+        if !KERNEL_SPACE.exclusive_access().insert_framed_area(
+            kernel_stack_bottom.into(),
+            kernel_stack_top.into(),
+            MapPermission::R | MapPermission::W,
+        ) {
+            return None;
+        }
+        Some(KernelStack { pid })
+    }
+
+    /// 内核栈栈顶
+    pub fn get_top(&self) -> usize {
+        let (_, kernel_stack_top) = kernel_stack_position(self.pid);
+        kernel_stack_top
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        let (kernel_stack_bottom, _) = kernel_stack_position(self.pid);
+        let kernel_stack_bottom_va: VirtAddr = kernel_stack_bottom.into();
+        KERNEL_SPACE
+            .exclusive_access()
+            .remove_area_with_start_vpn(kernel_stack_bottom_va.into());
+    }
+}
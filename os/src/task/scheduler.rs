@@ -0,0 +1,306 @@
+//! Scheduling policies
+//!
+//! [`TaskManager`](super::TaskManager)只负责任务的状态转换和切换，选择下一个任务的工作交给
+//! [`Scheduler`]。调度器只认识pid：任务变为Ready时通过`add`交给调度器，`fetch`取出下一个
+//! 要运行的任务（正在运行的任务不在调度器中），时钟中断时通过`on_tick`决定是否抢占。
+//!
+//! 具体使用哪种策略由cargo feature决定：`sched_stride`、`sched_priority`、`sched_mlfq`，
+//! 都没有开启时使用轮转调度。
+
+// 没有被feature选中的策略不会被构造
+#![allow(dead_code)]
+
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// 新任务的默认优先级
+pub const DEFAULT_PRIORITY: usize = 16;
+
+/// 调度策略
+pub trait Scheduler {
+    /// `pid`变为Ready，加入就绪队列
+    fn add(&mut self, pid: usize);
+    /// 取出下一个要运行的任务，没有Ready的任务时返回None
+    fn fetch(&mut self) -> Option<usize>;
+    /// 正在运行的`pid`经过了一个时钟周期，返回true表示需要抢占它
+    fn on_tick(&mut self, pid: usize) -> bool;
+    /// 设置`pid`的优先级，数值越大得到的cpu时间越多
+    fn set_priority(&mut self, _pid: usize, _priority: usize) {}
+    /// `pid`已经退出，清除调度器中和它相关的状态
+    fn remove(&mut self, _pid: usize) {}
+    /// `pid`所在的队列层级，只有多级反馈队列有意义，用于诊断
+    fn level(&self, _pid: usize) -> Option<usize> {
+        None
+    }
+}
+
+/// 轮转调度：先进先出，每个时钟周期都切换
+pub struct RoundRobinScheduler {
+    ready_queue: VecDeque<usize>,
+}
+
+impl RoundRobinScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RoundRobinScheduler {
+    fn add(&mut self, pid: usize) {
+        self.ready_queue.push_back(pid);
+    }
+
+    fn fetch(&mut self) -> Option<usize> {
+        self.ready_queue.pop_front()
+    }
+
+    fn on_tick(&mut self, _pid: usize) -> bool {
+        true
+    }
+}
+
+/// stride调度中所有stride的公倍数基准，stride = BIG_STRIDE / priority
+const BIG_STRIDE: u64 = 1 << 20;
+
+/// 每个任务在stride调度中的状态
+struct StrideInfo {
+    pass: u64,
+    priority: usize,
+}
+
+/// stride调度：每次选择pass最小的任务，运行后pass增加BIG_STRIDE / priority，
+/// 长期来看每个任务得到的cpu时间和优先级成正比
+pub struct StrideScheduler {
+    ready: BTreeSet<usize>,
+    infos: BTreeMap<usize, StrideInfo>,
+    // 最近一次被选中的任务的pass，新任务从这里开始，避免长时间独占cpu
+    current_pass: u64,
+}
+
+impl StrideScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready: BTreeSet::new(),
+            infos: BTreeMap::new(),
+            current_pass: 0,
+        }
+    }
+
+    fn info(&mut self, pid: usize) -> &mut StrideInfo {
+        let current_pass = self.current_pass;
+        self.infos.entry(pid).or_insert(StrideInfo {
+            pass: current_pass,
+            priority: DEFAULT_PRIORITY,
+        })
+    }
+}
+
+impl Scheduler for StrideScheduler {
+    fn add(&mut self, pid: usize) {
+        self.info(pid);
+        self.ready.insert(pid);
+    }
+
+    fn fetch(&mut self) -> Option<usize> {
+        // pass相同时选pid较小的，结果是确定的
+        let pid = *self
+            .ready
+            .iter()
+            .min_by_key(|pid| self.infos[pid].pass)?;
+        self.ready.remove(&pid);
+        let info = self.info(pid);
+        let pass = info.pass;
+        info.pass += BIG_STRIDE / info.priority as u64;
+        self.current_pass = pass;
+        Some(pid)
+    }
+
+    fn on_tick(&mut self, _pid: usize) -> bool {
+        true
+    }
+
+    fn set_priority(&mut self, pid: usize, priority: usize) {
+        self.info(pid).priority = priority;
+    }
+
+    fn remove(&mut self, pid: usize) {
+        self.ready.remove(&pid);
+        self.infos.remove(&pid);
+    }
+}
+
+/// 静态优先级调度：总是运行优先级最高的任务，优先级相同的任务之间轮转
+pub struct PriorityScheduler {
+    // (优先级, 加入顺序) -> pid，最后一项就是下一个要运行的任务
+    ready: BTreeMap<(usize, core::cmp::Reverse<usize>), usize>,
+    priorities: BTreeMap<usize, usize>,
+    // 递增的加入序号，保证同优先级先进先出
+    seq: usize,
+}
+
+impl PriorityScheduler {
+    pub fn new() -> Self {
+        Self {
+            ready: BTreeMap::new(),
+            priorities: BTreeMap::new(),
+            seq: 0,
+        }
+    }
+
+    fn priority(&self, pid: usize) -> usize {
+        *self.priorities.get(&pid).unwrap_or(&DEFAULT_PRIORITY)
+    }
+}
+
+impl Scheduler for PriorityScheduler {
+    fn add(&mut self, pid: usize) {
+        let key = (self.priority(pid), core::cmp::Reverse(self.seq));
+        self.seq += 1;
+        self.ready.insert(key, pid);
+    }
+
+    fn fetch(&mut self) -> Option<usize> {
+        let key = *self.ready.keys().next_back()?;
+        self.ready.remove(&key)
+    }
+
+    fn on_tick(&mut self, _pid: usize) -> bool {
+        // 被抢占的任务重新加入队列，如果它仍然是优先级最高的，还会被选中
+        true
+    }
+
+    fn set_priority(&mut self, pid: usize, priority: usize) {
+        self.priorities.insert(pid, priority);
+        // 已经在就绪队列中的任务需要按新的优先级重新排队
+        if let Some(key) = self
+            .ready
+            .iter()
+            .find(|(_, &ready_pid)| ready_pid == pid)
+            .map(|(key, _)| *key)
+        {
+            self.ready.remove(&key);
+            self.add(pid);
+        }
+    }
+
+    fn remove(&mut self, pid: usize) {
+        self.priorities.remove(&pid);
+        self.ready.retain(|_, ready_pid| *ready_pid != pid);
+    }
+}
+
+/// 多级反馈队列的层数，第0层优先级最高
+const MLFQ_LEVELS: usize = 3;
+/// 每一层的时间片（时钟周期数），层级越低时间片越长
+const MLFQ_SLICES: [usize; MLFQ_LEVELS] = [2, 4, 8];
+/// 每隔多少个时钟周期把所有任务提升回第0层，防止低层的任务饿死
+const MLFQ_BOOST_TICKS: usize = 100;
+
+/// 每个任务在多级反馈队列中的状态
+#[derive(Default)]
+struct MlfqInfo {
+    level: usize,
+    // 在当前层已经连续用掉的时钟周期数
+    used: usize,
+    // 因为更高层有任务而被抢占，重新加入队列时不算主动让出
+    preempted: bool,
+}
+
+/// 多级反馈队列：总是从最高的非空层取任务
+///
+/// 用完整个时间片的任务被降一层；时间片用完之前主动让出cpu的任务重新计数，留在原来的层；
+/// 每MLFQ_BOOST_TICKS个周期所有任务回到第0层
+pub struct MlfqScheduler {
+    queues: [VecDeque<usize>; MLFQ_LEVELS],
+    infos: BTreeMap<usize, MlfqInfo>,
+    ticks: usize,
+}
+
+impl MlfqScheduler {
+    pub fn new() -> Self {
+        Self {
+            queues: Default::default(),
+            infos: BTreeMap::new(),
+            ticks: 0,
+        }
+    }
+
+    fn boost(&mut self) {
+        for level in 1..MLFQ_LEVELS {
+            while let Some(pid) = self.queues[level].pop_front() {
+                self.queues[0].push_back(pid);
+            }
+        }
+        for info in self.infos.values_mut() {
+            *info = MlfqInfo::default();
+        }
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn add(&mut self, pid: usize) {
+        let info = self.infos.entry(pid).or_default();
+        // 主动让出的任务重新计数，被抢占的任务保留已经用掉的部分
+        if !info.preempted {
+            info.used = 0;
+        }
+        info.preempted = false;
+        self.queues[info.level].push_back(pid);
+    }
+
+    fn fetch(&mut self) -> Option<usize> {
+        self.queues.iter_mut().find_map(|queue| queue.pop_front())
+    }
+
+    fn on_tick(&mut self, pid: usize) -> bool {
+        self.ticks += 1;
+        if self.ticks % MLFQ_BOOST_TICKS == 0 {
+            self.boost();
+            return true;
+        }
+        let info = self.infos.entry(pid).or_default();
+        info.used += 1;
+        let level = info.level;
+        if info.used >= MLFQ_SLICES[level] {
+            info.level = (level + 1).min(MLFQ_LEVELS - 1);
+            info.used = 0;
+            return true;
+        }
+        // 更高层有任务在等待时立即让给它
+        let preempt = self.queues[..level].iter().any(|queue| !queue.is_empty());
+        self.infos.get_mut(&pid).unwrap().preempted = preempt;
+        preempt
+    }
+
+    fn remove(&mut self, pid: usize) {
+        self.infos.remove(&pid);
+        for queue in self.queues.iter_mut() {
+            queue.retain(|&ready_pid| ready_pid != pid);
+        }
+    }
+
+    fn level(&self, pid: usize) -> Option<usize> {
+        Some(self.infos.get(&pid).map_or(0, |info| info.level))
+    }
+}
+
+/// 由cargo feature选出的调度策略
+#[cfg(feature = "sched_stride")]
+pub type DefaultScheduler = StrideScheduler;
+/// 由cargo feature选出的调度策略
+#[cfg(all(feature = "sched_priority", not(feature = "sched_stride")))]
+pub type DefaultScheduler = PriorityScheduler;
+/// 由cargo feature选出的调度策略
+#[cfg(all(
+    feature = "sched_mlfq",
+    not(any(feature = "sched_stride", feature = "sched_priority"))
+))]
+pub type DefaultScheduler = MlfqScheduler;
+/// 由cargo feature选出的调度策略
+#[cfg(not(any(
+    feature = "sched_stride",
+    feature = "sched_priority",
+    feature = "sched_mlfq"
+)))]
+pub type DefaultScheduler = RoundRobinScheduler;
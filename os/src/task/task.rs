@@ -1,11 +1,21 @@
 //! Types related to task management
+use super::pid::{pid_alloc, KernelStack, PidHandle};
 use super::TaskContext;
 use crate::trap::{trap_handler, TrapContext};
-use crate::config::{kernel_stack_position, TRAP_CONTEXT};
-use crate::mm::{MapPermission, MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::config::{MAX_FD, MAX_SYSCALL_NUM, TRAP_CONTEXT};
+use crate::fs::{File, Stdin, Stdout};
+use crate::mm::{MemorySet, PhysPageNum, VirtAddr, VirtPageNum, KERNEL_SPACE};
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 
 // 任务控制块
 pub struct TaskControlBlock {
+    // 进程标识符，drop时回收
+    pub pid: PidHandle,
+    // 内核栈，drop时从KERNEL_SPACE中解除映射
+    pub kernel_stack: KernelStack,
     pub task_status: TaskStatus,
     pub task_cx: TaskContext,
     // 应用地址空间
@@ -16,6 +26,76 @@ pub struct TaskControlBlock {
     pub base_size: usize,
     pub heap_bottom: usize,
     pub program_brk: usize,
+    // 父进程的pid，内核直接创建的任务没有父进程
+    pub parent: Option<usize>,
+    // 退出码，任务退出后等待父进程通过waitpid回收
+    pub exit_code: i32,
+    // cpu时间和系统调用的统计
+    pub stats: TaskStats,
+    // 文件描述符表，下标即文件描述符，None表示空闲
+    pub fd_table: Vec<Option<Arc<dyn File>>>,
+}
+
+/// 任务运行情况的统计，时间的单位都是微秒
+pub struct TaskStats {
+    /// 第一次被调度的时间
+    pub first_time: Option<usize>,
+    /// 在用户态运行的时间
+    pub user_time: usize,
+    /// 在内核态运行的时间，不包括空闲等待
+    pub kernel_time: usize,
+    /// 被切换进来的次数
+    pub switch_count: usize,
+    /// 每个系统调用号被调用的次数，长度为MAX_SYSCALL_NUM，放在堆上以免占用内核栈
+    pub syscall_times: Box<[u32]>,
+    // 上一次记账的时间，之后的时间记在用户态还是内核态取决于下一次记账的位置
+    last_time: usize,
+}
+
+impl Default for TaskStats {
+    fn default() -> Self {
+        Self {
+            first_time: None,
+            user_time: 0,
+            kernel_time: 0,
+            switch_count: 0,
+            syscall_times: vec![0; MAX_SYSCALL_NUM].into_boxed_slice(),
+            last_time: 0,
+        }
+    }
+}
+
+impl TaskStats {
+    /// 任务被切换进来，从现在开始计时
+    pub fn on_schedule(&mut self, now: usize) {
+        self.first_time.get_or_insert(now);
+        self.switch_count += 1;
+        self.last_time = now;
+    }
+
+    /// 从用户态陷入内核，上一段时间记为用户态时间
+    pub fn charge_user(&mut self, now: usize) {
+        self.user_time += now - self.last_time;
+        self.last_time = now;
+    }
+
+    /// 回到用户态或者被切换出去，上一段时间记为内核态时间
+    pub fn charge_kernel(&mut self, now: usize) {
+        self.kernel_time += now - self.last_time;
+        self.last_time = now;
+    }
+
+    /// 到`now`为止一共用掉的cpu时间，包括还没有记账的这一段，只对正在运行的任务有意义
+    pub fn cpu_time(&self, now: usize) -> usize {
+        self.user_time + self.kernel_time + (now - self.last_time)
+    }
+
+    /// 记录一次系统调用
+    pub fn record_syscall(&mut self, syscall_id: usize) {
+        if let Some(times) = self.syscall_times.get_mut(syscall_id) {
+            *times += 1;
+        }
+    }
 }
 
 // 任务状态
@@ -24,13 +104,15 @@ pub enum TaskStatus {
     // UnInit,  // 未初始化
     Ready,   // 准备运行
     Running, // 正在运行
-    Exited,  // 已退出
+    Blocked, // 等待某个事件（例如睡眠到期），不会被调度
+    Exited,  // 已退出，等待父进程回收
 }
 
 impl TaskControlBlock {
-    pub fn new(elf_data: &[u8], app_id: usize) -> Self {
+    /// 从ELF创建一个任务，ELF无效或物理页耗尽时返回None
+    pub fn new(elf_data: &[u8]) -> Option<Self> {
         // 加载应用到内存中
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data)?;
 
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
@@ -41,14 +123,13 @@ impl TaskControlBlock {
 
         // map a kernel-stack in kernel space
         // 假如有两个应用：则内存分布为 内存顶部地址- 8kb内存 -（4kb间隔）- 8kb内存
-        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(app_id);
-        KERNEL_SPACE.exclusive_access().insert_framed_area(
-            kernel_stack_bottom.into(),
-            kernel_stack_top.into(),
-            MapPermission::R | MapPermission::W,
-        );
+        let pid = pid_alloc().expect("no pid left");
+        let kernel_stack = KernelStack::new(&pid)?;
+        let kernel_stack_top = kernel_stack.get_top();
 
         let task_control_block = Self {
+            pid,
+            kernel_stack,
             task_status,
             // ra被设置为 trap_return ，任务切换__switch执行完毕后，再去执行该方法
             task_cx: TaskContext::goto_trap_return(kernel_stack_top),
@@ -59,6 +140,15 @@ impl TaskControlBlock {
             // 即栈顶位置
             heap_bottom: user_sp,
             program_brk: user_sp,
+            parent: None,
+            exit_code: 0,
+            stats: TaskStats::default(),
+            // 0、1、2分别是标准输入、标准输出和标准错误
+            fd_table: vec![
+                Some(Arc::new(Stdin)),
+                Some(Arc::new(Stdout)),
+                Some(Arc::new(Stdout)),
+            ],
         };
 
         // 获取trap_cx，这里是引用内存，但没有实际应用，不需要申请，from_elf的时候已经申请好，即TRAP_CONTEXT - TRAMPOLINE
@@ -77,7 +167,83 @@ impl TaskControlBlock {
             trap_handler as usize,
         );
 
-        task_control_block
+        Some(task_control_block)
+    }
+
+    /// 复制当前任务的地址空间，创建一个子任务，pid或物理页用完时返回None
+    pub fn fork(&mut self) -> Option<Self> {
+        let pid = pid_alloc()?;
+        // 用户页面与子任务写时复制共享，只有Trap上下文所在的物理页被立即复制
+        let memory_set = MemorySet::from_existed_user(&mut self.memory_set)?;
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+        let kernel_stack = KernelStack::new(&pid)?;
+        let kernel_stack_top = kernel_stack.get_top();
+        let task_control_block = Self {
+            pid,
+            kernel_stack,
+            task_status: TaskStatus::Ready,
+            task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+            memory_set,
+            trap_cx_ppn,
+            base_size: self.base_size,
+            heap_bottom: self.heap_bottom,
+            program_brk: self.program_brk,
+            parent: Some(self.getpid()),
+            exit_code: 0,
+            stats: TaskStats::default(),
+            // 子任务和父任务共享打开的文件
+            fd_table: self.fd_table.clone(),
+        };
+        // 子任务陷入内核时需要使用自己的内核栈
+        task_control_block.get_trap_cx().kernel_sp = kernel_stack_top;
+        Some(task_control_block)
+    }
+
+    /// 用新的ELF替换当前任务的地址空间，pid与内核栈保持不变
+    ///
+    /// ELF无效时返回false，当前任务的地址空间保持不变
+    pub fn exec(&mut self, elf_data: &[u8]) -> bool {
+        let (memory_set, user_sp, entry_point) = match MemorySet::from_elf(elf_data) {
+            Some(loaded) => loaded,
+            None => return false,
+        };
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap()
+            .ppn();
+        // 旧的地址空间在这里被drop，物理页被回收
+        self.memory_set = memory_set;
+        self.trap_cx_ppn = trap_cx_ppn;
+        self.base_size = user_sp;
+        self.heap_bottom = user_sp;
+        self.program_brk = user_sp;
+        *self.get_trap_cx() = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.exclusive_access().token(),
+            self.kernel_stack.get_top(),
+            trap_handler as usize,
+        );
+        true
+    }
+
+    /// 分配一个最小的空闲文件描述符，已经打开了MAX_FD个文件时返回None
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        if let Some(fd) = self.fd_table.iter().position(|file| file.is_none()) {
+            Some(fd)
+        } else if self.fd_table.len() < MAX_FD {
+            self.fd_table.push(None);
+            Some(self.fd_table.len() - 1)
+        } else {
+            None
+        }
+    }
+
+    pub fn getpid(&self) -> usize {
+        self.pid.0
     }
 
     pub fn get_trap_cx(&self) -> &'static mut TrapContext {
@@ -88,6 +254,20 @@ impl TaskControlBlock {
         self.memory_set.token()
     }
 
+    /// 解除[start, end)的映射，范围和堆重叠时返回false
+    ///
+    /// 堆由sbrk按起始地址查找并调整大小，被切开后会和拆出去的逻辑段重叠
+    pub fn munmap(&mut self, start: VirtAddr, end: VirtAddr) -> bool {
+        let (start_vpn, end_vpn) = (start.floor(), end.ceil());
+        let heap_start = VirtAddr(self.heap_bottom).floor();
+        // 堆为空时也不能把它的逻辑段整个删掉
+        let heap_end = VirtAddr(self.program_brk).ceil().max(VirtPageNum(heap_start.0 + 1));
+        if start_vpn < heap_end && heap_start < end_vpn {
+            return false;
+        }
+        self.memory_set.remove_area_range(start, end)
+    }
+
     /// change the location of the program break. return None if failed.
     pub fn change_program_brk(&mut self, size: i32) -> Option<usize> {
         let old_break = self.program_brk;
@@ -1,9 +1,16 @@
+use core::cmp::Ordering;
 use riscv::register::time;
-use crate::config::CLOCK_FREQ;
+use crate::dtb::clock_freq;
+use crate::drivers::rtc::unix_time_ns;
 use crate::sbi::set_timer;
+use crate::sync::UPSafeCell;
+use crate::task::wakeup_task;
+use alloc::collections::BinaryHeap;
+use lazy_static::*;
 
 const TICKS_PER_SEC: usize = 100;
 const MICRO_PER_SEC: usize = 1_000_000;
+const NANO_PER_SEC: usize = 1_000_000_000;
 
 // 获得mtime计数器的值(M特全级，靠SEE 即RustSBI预留接口)
 pub fn get_time() -> usize {
@@ -12,10 +19,123 @@ pub fn get_time() -> usize {
 
 // 设置mtimecmp，计算出 10ms 之内计数器的增量，设置下一次中断。
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+    set_timer(get_time() + clock_freq() / TICKS_PER_SEC);
 }
 
 // 统计应用的运行时长，以微秒为单位返回当前计数器的值
 pub fn get_time_us() -> usize {
-    get_time() / (CLOCK_FREQ / MICRO_PER_SEC)
+    get_time() / (clock_freq() / MICRO_PER_SEC)
+}
+
+/// 开机以来的纳秒数，先拆出整秒避免乘法溢出
+pub fn get_time_ns() -> usize {
+    let time = get_time();
+    let freq = clock_freq();
+    time / freq * NANO_PER_SEC + time % freq * NANO_PER_SEC / freq
+}
+
+lazy_static! {
+    /// 开机时刻的Unix时间（纳秒），只读一次RTC，之后由time计数器推算，保证和单调时钟同步增长
+    static ref BOOT_REALTIME_NS: usize =
+        unix_time_ns().map_or(0, |now| now.saturating_sub(get_time_ns()));
+}
+
+/// 当前的Unix时间（纳秒），没有RTC时从开机算起
+pub fn realtime_ns() -> usize {
+    *BOOT_REALTIME_NS + get_time_ns()
+}
+
+/// 和Linux中的struct timespec一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_ns(ns: usize) -> Self {
+        Self {
+            sec: ns / NANO_PER_SEC,
+            nsec: ns % NANO_PER_SEC,
+        }
+    }
+}
+
+/// 和Linux中的struct timeval一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    pub fn from_ns(ns: usize) -> Self {
+        Self {
+            sec: ns / NANO_PER_SEC,
+            usec: ns % NANO_PER_SEC / 1000,
+        }
+    }
+}
+
+/// 一个睡眠中的任务，到`expire_us`时被唤醒
+struct Timer {
+    expire_us: usize,
+    pid: usize,
+}
+
+// BinaryHeap是大顶堆，反过来比较让最早到期的排在堆顶
+impl PartialEq for Timer {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_us == other.expire_us
+    }
+}
+
+impl Eq for Timer {}
+
+impl PartialOrd for Timer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Timer {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.expire_us.cmp(&self.expire_us)
+    }
+}
+
+lazy_static! {
+    /// 按到期时间排序的定时器队列
+    static ref TIMERS: UPSafeCell<BinaryHeap<Timer>> = unsafe { UPSafeCell::new(BinaryHeap::new()) };
+}
+
+/// 让`pid`在`expire_us`时被唤醒
+pub fn add_timer(expire_us: usize, pid: usize) {
+    TIMERS.exclusive_access().push(Timer { expire_us, pid });
+}
+
+/// 任务退出时移除它的定时器，避免pid被复用后唤醒了别的任务
+pub fn remove_timer(pid: usize) {
+    let mut timers = TIMERS.exclusive_access();
+    let remaining: BinaryHeap<Timer> = timers.drain().filter(|timer| timer.pid != pid).collect();
+    *timers = remaining;
+}
+
+/// 唤醒所有已经到期的任务，在每次时钟中断时调用
+pub fn check_timer() {
+    let current_us = get_time_us();
+    loop {
+        let mut timers = TIMERS.exclusive_access();
+        match timers.peek() {
+            Some(timer) if timer.expire_us <= current_us => {
+                let pid = timers.pop().unwrap().pid;
+                // 唤醒时会访问TASK_MANAGER，先释放定时器队列
+                drop(timers);
+                wakeup_task(pid);
+            }
+            _ => break,
+        }
+    }
 }
@@ -1,7 +1,11 @@
 //! Trap handling functionality
 //!
-//! For rCore, we have a single trap entry point, namely `__alltraps`. At
-//! initialization in [`init()`], we set the `stvec` CSR to point to it.
+//! For rCore, we have a single trap entry point from userspace, namely
+//! `__alltraps`. `stvec` points to it only while running user code; inside the
+//! kernel it points to `__alltraps_k`, which saves the registers on the current
+//! stack and calls [`trap_from_kernel()`]. The kernel only enables interrupts
+//! while idle (see [`wait_for_interrupt()`]), so timer and device interrupts
+//! are the only traps it expects; anything else is fatal.
 //!
 //! All traps go through `__alltraps`, which is defined in `trap.S`. The
 //! assembly language code does just enough work restore the kernel space
@@ -14,59 +18,101 @@
 
 mod context;
 use crate::config::{TRAMPOLINE, TRAP_CONTEXT};
-use crate::{syscall::syscall, timer::set_next_trigger, task::suspend_current_and_run_next};
-use crate::task::current_user_token;
+use crate::{syscall::syscall, timer::{check_timer, set_next_trigger}, task::on_timer_tick};
+use crate::drivers::plic::handle_external_interrupt;
+use crate::mm::MapPermission;
+use crate::task::{
+    account_trap_enter, account_trap_return, current_trap_cx, current_user_token,
+    exit_current_and_run_next, handle_page_fault, record_syscall,
+};
 
 use core::arch::{asm, global_asm};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Trap, Interrupt},
-    stval, stvec, sie
+    sepc, sstatus, stval, stvec, sie
 };
 
 // 在批处理操作系统初始化的时候，我们需要修改 stvec 寄存器来指向正确的 Trap 处理入口点。
 global_asm!(include_str!("trap.S"));
 
-/// initialize CSR `stvec` as the entry of `__alltraps`
+/// 应用触发非法访存被内核杀死时的退出码
+pub const EXIT_CODE_PAGE_FAULT: i32 = -2;
+/// 应用执行非法指令被内核杀死时的退出码
+pub const EXIT_CODE_ILLEGAL_INSTRUCTION: i32 = -3;
+/// 应用触发其他异常（断点、地址未对齐等）被内核杀死时的退出码
+pub const EXIT_CODE_EXCEPTION: i32 = -4;
+
+/// initialize CSR `stvec`
 /// 在 RISC-V 架构中，stvec 寄存器用于设置中断和异常处理的向量表地址。
-/// 通过将 __alltraps 函数的地址作为参数传递给 stvec::write 函数，将中断和异常处理的入口点设置为 __alltraps 函数。
+/// 内核态下的trap交给__alltraps_k，回到用户态之前才切换成跳板上的__alltraps
 /// TrapMode::Direct 表示使用直接模式，即将异常直接传递给入口点，而不进行额外的中间处理
 pub fn init() {
+    set_kernel_trap_entry();
+}
+
+fn set_kernel_trap_entry() {
     extern "C" {
-        fn __alltraps();
+        fn __alltraps_k();
     }
     unsafe {
-        stvec::write(__alltraps as usize, TrapMode::Direct);
+        stvec::write(__alltraps_k as usize, TrapMode::Direct);
     }
 }
 
 #[no_mangle]
 /// handle an interrupt, exception, or system call from user space
-/// a0寄存器保存了trap之前寄存器的内容
-pub fn trap_handler(cx: &mut TrapContext) -> &mut TrapContext {
+/// __alltraps通过jr跳转过来，没有参数也不会返回，Trap上下文通过当前任务获取，处理完后直接trap_return
+pub fn trap_handler() -> ! {
+    // 处理期间如果内核自己出错，不能再走用户态的__alltraps
+    set_kernel_trap_entry();
+    account_trap_enter();
     let scause = scause::read(); // get trap cause
     let stval = stval::read(); // get extra value
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
+            let mut cx = current_trap_cx();
             cx.sepc += 4;
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
+            record_syscall(cx.x[17]);
+            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]);
+            // sys_exec会替换地址空间，Trap上下文所在的物理页也随之改变，需要重新获取
+            cx = current_trap_cx();
+            cx.x[10] = result as usize;
         }
-        Trap::Exception(Exception::StoreFault) | Trap::Exception(Exception::StorePageFault) => {
-            println!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.", stval, cx.sepc);
-            panic!("[kernel] Cannot continue!");
-            //run_next_app();
+        // 懒分配的页面第一次被访问，分配物理页后回到用户态重新执行这条指令
+        Trap::Exception(Exception::LoadPageFault) => {
+            handle_user_page_fault(stval, MapPermission::R);
+        }
+        Trap::Exception(Exception::StorePageFault) => {
+            handle_user_page_fault(stval, MapPermission::W);
+        }
+        Trap::Exception(Exception::InstructionPageFault) => {
+            handle_user_page_fault(stval, MapPermission::X);
+        }
+        Trap::Exception(Exception::StoreFault)
+        | Trap::Exception(Exception::LoadFault)
+        | Trap::Exception(Exception::InstructionFault) => {
+            kill_current(scause.cause(), stval, EXIT_CODE_PAGE_FAULT);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
-            println!("[kernel] IllegalInstruction in application, kernel killed it.");
-            panic!("[kernel] Cannot continue!");
-            //run_next_app();
+            kill_current(scause.cause(), stval, EXIT_CODE_ILLEGAL_INSTRUCTION);
         }
         // 抢占式调度
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             set_next_trigger();
-            suspend_current_and_run_next();
+            // 先唤醒睡眠到期的任务，它们可以参与这一次调度
+            check_timer();
+            on_timer_tick();
+        }
+        // 设备中断由PLIC分发给各个驱动
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            handle_external_interrupt();
         }
-        _ => {
+        // 其余用户态异常都是应用自己的问题，只结束这个应用
+        Trap::Exception(_) => {
+            kill_current(scause.cause(), stval, EXIT_CODE_EXCEPTION);
+        }
+        Trap::Interrupt(_) => {
             panic!(
                 "Unsupported trap {:?}, stval = {:#x}!",
                 scause.cause(),
@@ -74,7 +120,60 @@ pub fn trap_handler(cx: &mut TrapContext) -> &mut TrapContext {
             );
         }
     }
-    cx
+    trap_return();
+}
+
+/// 缺页异常：懒加载的页面在这里分配，其余情况说明应用访问了非法地址或物理页已经耗尽
+fn handle_user_page_fault(stval: usize, access: MapPermission) {
+    if !handle_page_fault(stval, access) {
+        kill_current(scause::read().cause(), stval, EXIT_CODE_PAGE_FAULT);
+    }
+}
+
+/// 用户态异常只影响出错的应用：打印诊断信息，以`exit_code`结束它并切换到下一个任务
+fn kill_current(cause: Trap, stval: usize, exit_code: i32) {
+    println!(
+        "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.",
+        cause,
+        stval,
+        current_trap_cx().sepc
+    );
+    exit_current_and_run_next(exit_code);
+}
+
+#[no_mangle]
+/// 内核态下的trap：空闲时的时钟中断只需要唤醒到期的任务，由空闲循环负责调度；
+/// 设备中断交给驱动处理；其他trap说明内核自己出了问题，直接panic
+pub extern "C" fn trap_from_kernel(_cx: &mut TrapContext) {
+    match scause::read().cause() {
+        Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            set_next_trigger();
+            check_timer();
+        }
+        Trap::Interrupt(Interrupt::SupervisorExternal) => {
+            handle_external_interrupt();
+        }
+        cause => {
+            panic!(
+                "a trap {:?} from kernel, stval = {:#x}, sepc = {:#x}!",
+                cause,
+                stval::read(),
+                sepc::read()
+            );
+        }
+    }
+}
+
+/// 打开中断，等待并处理下一个中断
+///
+/// 先在关中断的状态下wfi，中断到来之前已经待处理的中断也会让wfi返回，不会丢失唤醒
+pub fn wait_for_interrupt() {
+    unsafe {
+        asm!("wfi");
+        // 打开中断后待处理的中断立即进入trap_from_kernel
+        sstatus::set_sie();
+        sstatus::clear_sie();
+    }
 }
 
 /// sstatus.sie = 1，置0则屏蔽中断
@@ -84,6 +183,13 @@ pub fn enable_timer_interrupt() {
     }
 }
 
+/// 打开S态外部中断，设备中断经过PLIC到达
+pub fn enable_external_interrupt() {
+    unsafe {
+        sie::set_sext();
+    }
+}
+
 pub use context::TrapContext;
 
 fn set_user_trap_entry() {
@@ -97,6 +203,7 @@ fn set_user_trap_entry() {
 /// set the reg a0 = trap_cx_ptr, reg a1 = phy addr of usr page table,
 /// finally, jump to new addr of __restore asm function
 pub fn trap_return() -> ! {
+    account_trap_return();
     set_user_trap_entry();
     let trap_cx_ptr = TRAP_CONTEXT;
     let user_satp = current_user_token();
@@ -4,18 +4,37 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::yield_;
-use user_lib::get_time;
+use user_lib::{get_time, nanosleep, sleep, TimeSpec};
+
+// get_time的单位是微秒
+const SLEEP_MS: usize = 1000;
+const NANOSLEEP: TimeSpec = TimeSpec {
+    sec: 0,
+    nsec: 500_000_000,
+};
 
 #[no_mangle]
 fn main() -> i32 {
-    let current_timer = get_time();
-    let wait_for = current_timer + 10 * 100_0000;
+    let start = get_time();
+    sleep(SLEEP_MS);
+    let elapsed = get_time() - start;
+    println!("sleep({}) took {} us", SLEEP_MS, elapsed);
+    assert!(elapsed >= (SLEEP_MS * 1000) as isize);
+
+    let start = get_time();
+    let mut rem = TimeSpec { sec: 1, nsec: 1 };
+    assert_eq!(nanosleep(&NANOSLEEP, Some(&mut rem)), 0);
+    let elapsed = get_time() - start;
+    println!("nanosleep(0.5s) took {} us", elapsed);
+    assert!(elapsed >= (NANOSLEEP.nsec / 1000) as isize);
+    assert_eq!((rem.sec, rem.nsec), (0, 0));
 
-    // 1秒=100 0000微秒
-    while get_time() < wait_for {
-        println!("running test 03 sleep, time: {} wait_for: {}", get_time(), wait_for);
-    }
-    println!("Test sleep OK! {}", get_time());
+    // nsec超过1秒是非法参数
+    let invalid = TimeSpec {
+        sec: 0,
+        nsec: 1_000_000_000,
+    };
+    assert_eq!(nanosleep(&invalid, None), -1);
+    println!("Test sleep OK!");
     0
 }
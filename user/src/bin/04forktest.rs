@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::syscall::sys_waitpid;
+use user_lib::{
+    close, exec, exit, fork, getpid, open, read, unlink, wait, waitpid, write, OpenFlags,
+};
+
+const MAX_CHILD: usize = 5;
+// 用户地址空间的低处没有映射
+const BAD_ADDR: usize = 0x8;
+const JUNK_APP: &str = "04junk\0";
+
+/// 把`content`写进名为JUNK_APP的文件
+fn write_junk_app(content: &[u8]) {
+    let fd = open(JUNK_APP, OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, content), content.len() as isize);
+    assert_eq!(close(fd as usize), 0);
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!("forktest: parent pid = {}", getpid());
+    for i in 0..MAX_CHILD {
+        let pid = fork();
+        if pid == 0 {
+            println!("I am child {}, pid = {}", i, getpid());
+            exit(100 + i as i32);
+        }
+        assert!(pid > 0, "fork failed");
+    }
+
+    // 每个子进程的退出码都应该被父进程收回
+    let mut exit_code: i32 = 0;
+    let mut sum = 0;
+    for _ in 0..MAX_CHILD {
+        let pid = wait(&mut exit_code);
+        assert!(pid > 0, "wait stopped early");
+        sum += exit_code;
+    }
+    assert_eq!(sum, (100..100 + MAX_CHILD as i32).sum::<i32>());
+    assert!(wait(&mut exit_code) < 0, "wait got too many");
+
+    // 不存在的应用exec失败
+    assert_eq!(exec("no_such_app\0"), -1);
+
+    // 不是ELF的文件、只有文件头而程序头表被截断的ELF都不能exec
+    write_junk_app(b"definitely not an ELF file");
+    assert_eq!(exec(JUNK_APP), -1);
+    let mut header = [0u8; 64];
+    let fd = open("00write_a\0", OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    assert_eq!(read(fd as usize, &mut header), header.len() as isize);
+    assert_eq!(close(fd as usize), 0);
+    write_junk_app(&header);
+    assert_eq!(exec(JUNK_APP), -1);
+    // 打开着的文件不能删除，删除之后就找不到了
+    let fd = open(JUNK_APP, OpenFlags::RDONLY);
+    assert!(fd >= 0);
+    assert_eq!(unlink(JUNK_APP), -1);
+    assert_eq!(close(fd as usize), 0);
+    assert_eq!(unlink(JUNK_APP), 0);
+    assert_eq!(open(JUNK_APP, OpenFlags::RDONLY), -1);
+    assert_eq!(unlink(JUNK_APP), -1);
+
+    // 子进程exec 00write_a，它的退出码是0
+    let pid = fork();
+    if pid == 0 {
+        exec("00write_a\0");
+        panic!("unreachable after exec!");
+    }
+    // 退出码的地址无效时返回-1，子进程不会被回收
+    assert_eq!(sys_waitpid(pid, BAD_ADDR as *mut i32), -1);
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("forktest pass.");
+    0
+}
@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+use user_lib::{
+    run_in_child, EXIT_CODE_EXCEPTION, EXIT_CODE_ILLEGAL_INSTRUCTION, EXIT_CODE_PAGE_FAULT,
+};
+
+fn store_to_null() {
+    unsafe {
+        core::ptr::null_mut::<u8>().write_volatile(0);
+    }
+}
+
+fn privileged_instruction() {
+    unsafe {
+        asm!("sret");
+    }
+}
+
+fn breakpoint() {
+    unsafe {
+        asm!("ebreak");
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(run_in_child(store_to_null), EXIT_CODE_PAGE_FAULT);
+    println!("store to null killed the child only");
+    assert_eq!(run_in_child(privileged_instruction), EXIT_CODE_ILLEGAL_INSTRUCTION);
+    println!("privileged instruction killed the child only");
+    assert_eq!(run_in_child(breakpoint), EXIT_CODE_EXCEPTION);
+    println!("breakpoint killed the child only");
+    println!("fault_kill pass.");
+    0
+}
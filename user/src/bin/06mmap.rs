@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap, run_in_child, sbrk, EXIT_CODE_PAGE_FAULT};
+
+const PAGE_SIZE: usize = 0x1000;
+const START: usize = 0x1000_0000;
+const PROT_R: usize = 1;
+const PROT_W: usize = 2;
+
+fn page(i: usize) -> *mut u8 {
+    (START + i * PAGE_SIZE) as *mut u8
+}
+
+fn write_unmapped_page() {
+    unsafe {
+        page(1).write_volatile(0);
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(mmap(START, 3 * PAGE_SIZE, PROT_R | PROT_W), 0);
+    for i in 0..3 {
+        unsafe {
+            page(i).write_volatile(i as u8 + 1);
+        }
+    }
+    for i in 0..3 {
+        assert_eq!(unsafe { page(i).read_volatile() }, i as u8 + 1);
+    }
+    println!("mmap read/write ok");
+
+    // 重叠的区域和非法的prot都应该失败
+    assert_eq!(mmap(START + PAGE_SIZE, PAGE_SIZE, PROT_R), -1);
+    assert_eq!(mmap(START + 3 * PAGE_SIZE, PAGE_SIZE, 0), -1);
+    assert_eq!(mmap(START + 3 * PAGE_SIZE, PAGE_SIZE, 8), -1);
+    assert_eq!(mmap(START + 1, PAGE_SIZE, PROT_R), -1);
+    // 只写不读的页表项是保留的
+    assert_eq!(mmap(START + 3 * PAGE_SIZE, PAGE_SIZE, PROT_W), -1);
+
+    // 从中间解除映射，两侧的页面保持不变
+    assert_eq!(munmap(START + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(munmap(START + PAGE_SIZE, PAGE_SIZE), -1);
+    assert_eq!(unsafe { page(0).read_volatile() }, 1);
+    assert_eq!(unsafe { page(2).read_volatile() }, 3);
+
+    assert_eq!(run_in_child(write_unmapped_page), EXIT_CODE_PAGE_FAULT);
+    println!("access to unmapped page killed the child");
+
+    // 空洞可以重新映射，新页面内容为0
+    assert_eq!(mmap(START + PAGE_SIZE, PAGE_SIZE, PROT_R | PROT_W), 0);
+    assert_eq!(unsafe { page(1).read_volatile() }, 0);
+    assert_eq!(munmap(START, 3 * PAGE_SIZE), 0);
+
+    // 堆由sbrk管理，不能被munmap切开
+    let heap_bottom = sbrk(0);
+    assert!(heap_bottom > 0);
+    assert_eq!(sbrk(2 * PAGE_SIZE as i32), heap_bottom);
+    assert_eq!(munmap(heap_bottom as usize + PAGE_SIZE, PAGE_SIZE), -1);
+    assert_eq!(sbrk(-2 * PAGE_SIZE as i32), heap_bottom + 2 * PAGE_SIZE as isize);
+    println!("mmap pass.");
+    0
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{fork, wait, yield_};
+
+const LEN: usize = 4096 * 2;
+
+// 跨越多个页面的全局数据，fork后父子任务先共享这些页面
+static mut DATA: [u8; LEN] = [0; LEN];
+
+fn check(value: u8) {
+    unsafe {
+        assert!(DATA.iter().all(|&b| b == value));
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    unsafe {
+        DATA.fill(1);
+    }
+    let mut on_stack: usize = 10;
+    let pid = fork();
+    if pid == 0 {
+        // 子任务的写入不能影响父任务
+        check(1);
+        unsafe {
+            DATA.fill(2);
+        }
+        on_stack = 20;
+        yield_();
+        check(2);
+        assert_eq!(on_stack, 20);
+        println!("child sees its own copy");
+        return 0;
+    }
+    // 父任务的写入也不能影响子任务
+    unsafe {
+        DATA[0] = 3;
+        DATA[0] = 1;
+    }
+    on_stack += 1;
+    let mut exit_code: i32 = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    check(1);
+    assert_eq!(on_stack, 11);
+    // 子任务退出后只剩自己引用这些页面，写入仍然正常
+    unsafe {
+        DATA.fill(4);
+    }
+    check(4);
+    println!("cow pass.");
+    0
+}
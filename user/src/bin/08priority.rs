@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, get_time, set_priority, wait};
+
+// 不低于默认优先级16：静态优先级调度下，忙等的shell和父进程不会饿死子进程
+const PRIORITIES: [isize; 3] = [16, 32, 64];
+// 所有子进程一起运行1秒
+const RUN_US: isize = 1_000_000;
+
+/// 一直计数到deadline，返回计了多少次
+fn spin_until(deadline: isize) -> usize {
+    let mut count = 0;
+    while get_time() < deadline {
+        count += 1;
+    }
+    count
+}
+
+/// 几个优先级不同的cpu密集型进程同时运行，打印各自完成的工作量
+///
+/// 轮转调度下工作量大致相同；stride调度下和优先级大致成正比；
+/// 静态优先级调度下优先级最高的进程几乎占满整段时间
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(set_priority(1), -1);
+    assert_eq!(set_priority(16), 16);
+
+    let deadline = get_time() + RUN_US;
+    for &prio in PRIORITIES.iter() {
+        if fork() == 0 {
+            set_priority(prio);
+            let count = spin_until(deadline);
+            println!(
+                "priority {}: count = {}, count / priority = {}",
+                prio,
+                count,
+                count / prio as usize
+            );
+            exit(0);
+        }
+    }
+    let mut exit_code: i32 = 0;
+    for _ in PRIORITIES.iter() {
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+    println!("priority pass.");
+    0
+}
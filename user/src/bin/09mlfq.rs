@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, get_time, getpid, sched_level, wait, yield_};
+
+// 和内核中多级反馈队列的层数保持一致
+const LOWEST_LEVEL: isize = 2;
+const TIMEOUT_US: isize = 2_000_000;
+
+/// cpu密集型：一直运行，应该被降到最低层
+fn cpu_bound() -> i32 {
+    let pid = getpid() as usize;
+    let deadline = get_time() + TIMEOUT_US;
+    while get_time() < deadline {
+        if sched_level(pid) == LOWEST_LEVEL {
+            println!("cpu-bound child reached level {}", LOWEST_LEVEL);
+            return 0;
+        }
+    }
+    println!("cpu-bound child stayed at level {}", sched_level(pid));
+    -1
+}
+
+/// 交互型：每次很快主动让出，应该一直留在第0层
+fn yield_heavy() -> i32 {
+    let pid = getpid() as usize;
+    for _ in 0..200 {
+        yield_();
+        if sched_level(pid) != 0 {
+            println!("yield-heavy child was demoted to {}", sched_level(pid));
+            return -1;
+        }
+    }
+    println!("yield-heavy child stayed at level 0");
+    0
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    if sched_level(getpid() as usize) < 0 {
+        println!("not running the MLFQ scheduler, skipped.");
+        return 0;
+    }
+    for child in [cpu_bound as fn() -> i32, yield_heavy] {
+        if fork() == 0 {
+            exit(child());
+        }
+    }
+    let mut exit_code: i32 = 0;
+    for _ in 0..2 {
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+    println!("mlfq pass.");
+    0
+}
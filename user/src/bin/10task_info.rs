@@ -0,0 +1,69 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, exit, fork, get_time, getpid, pipe, read, sleep, task_info, waitpid, yield_, TaskInfo,
+};
+
+// 和内核中的系统调用号保持一致
+const SYSCALL_YIELD: usize = 124;
+const SYSCALL_GETPID: usize = 172;
+const SYSCALL_FORK: usize = 220;
+
+const GETPID_CALLS: u32 = 10;
+const YIELD_CALLS: u32 = 5;
+const BUSY_US: isize = 100_000;
+
+#[no_mangle]
+fn main() -> i32 {
+    let pid = getpid() as usize;
+    for _ in 1..GETPID_CALLS {
+        getpid();
+    }
+    for _ in 0..YIELD_CALLS {
+        yield_();
+    }
+    // 在用户态忙等一段时间
+    let deadline = get_time() + BUSY_US;
+    while get_time() < deadline {}
+    sleep(10);
+
+    let mut info = TaskInfo::default();
+    assert_eq!(task_info(pid, &mut info), 0);
+    println!(
+        "pid {}: first scheduled at {} us, user {} us, kernel {} us, {} switches",
+        pid, info.first_time, info.user_time, info.kernel_time, info.switch_count
+    );
+    assert!(info.first_time > 0);
+    assert!(info.user_time + info.kernel_time > 0);
+    assert_eq!(info.syscall_times[SYSCALL_GETPID], GETPID_CALLS);
+    assert_eq!(info.syscall_times[SYSCALL_YIELD], YIELD_CALLS);
+    // 每次yield和sleep都会被切换出去再切换回来
+    assert!(info.switch_count > YIELD_CALLS as usize);
+
+    // 已经退出但还没有被回收的子进程也可以查询
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let child = fork();
+    if child == 0 {
+        // 孙进程交给initproc回收
+        fork();
+        exit(0);
+    }
+    // 子进程和孙进程退出时关闭各自的写端，之后才能读到EOF
+    assert_eq!(close(pipe_fd[1]), 0);
+    assert_eq!(read(pipe_fd[0], &mut [0u8; 1]), 0);
+    assert_eq!(close(pipe_fd[0]), 0);
+    let mut child_info = TaskInfo::default();
+    assert_eq!(task_info(child as usize, &mut child_info), 0);
+    assert_eq!(child_info.syscall_times[SYSCALL_FORK], 1);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    // 回收之后就不存在了
+    assert_eq!(task_info(child as usize, &mut child_info), -1);
+    println!("task_info test passed!");
+    0
+}
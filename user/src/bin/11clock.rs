@@ -0,0 +1,54 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    clock_gettime, get_time, sleep, TimeSpec, CLOCK_MONOTONIC, CLOCK_PROCESS_CPUTIME_ID,
+    CLOCK_REALTIME,
+};
+
+const NANO_PER_SEC: usize = 1_000_000_000;
+const BUSY_NS: usize = 200_000_000;
+const SLEEP_MS: usize = 200;
+
+fn now(clock_id: usize) -> usize {
+    let mut ts = TimeSpec::default();
+    assert_eq!(clock_gettime(clock_id, &mut ts), 0);
+    assert!(ts.nsec < NANO_PER_SEC);
+    ts.sec * NANO_PER_SEC + ts.nsec
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    println!(
+        "realtime {} ns, monotonic {} ns, get_time {} us",
+        now(CLOCK_REALTIME),
+        now(CLOCK_MONOTONIC),
+        get_time()
+    );
+
+    // 忙等时cpu时间和单调时钟一起增长
+    let mono_start = now(CLOCK_MONOTONIC);
+    let cpu_start = now(CLOCK_PROCESS_CPUTIME_ID);
+    while now(CLOCK_MONOTONIC) - mono_start < BUSY_NS {}
+    let cpu_busy = now(CLOCK_PROCESS_CPUTIME_ID) - cpu_start;
+    println!("busy for {} ns, cpu time {} ns", BUSY_NS, cpu_busy);
+    assert!(cpu_busy > 0);
+
+    // 睡眠时不占用cpu
+    let mono_start = now(CLOCK_MONOTONIC);
+    let cpu_start = now(CLOCK_PROCESS_CPUTIME_ID);
+    sleep(SLEEP_MS);
+    let slept = now(CLOCK_MONOTONIC) - mono_start;
+    let cpu_sleep = now(CLOCK_PROCESS_CPUTIME_ID) - cpu_start;
+    println!("slept {} ns, cpu time {} ns", slept, cpu_sleep);
+    assert!(slept >= SLEEP_MS * 1_000_000);
+    assert!(cpu_sleep < slept / 2);
+
+    let mut ts = TimeSpec::default();
+    assert_eq!(clock_gettime(100, &mut ts), -1);
+    println!("clock test passed!");
+    0
+}
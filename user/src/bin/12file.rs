@@ -0,0 +1,119 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use user_lib::syscall::{
+    syscall, SYSCALL_FSTAT, SYSCALL_OPEN, SYSCALL_PIPE, SYSCALL_READ, SYSCALL_WRITE,
+};
+use user_lib::{close, fstat, open, pipe, read, unlink, write, OpenFlags, Stat, StatMode};
+
+const FILE_NAME: &str = "12file_test\0";
+const CONTENT: &[u8] = b"Hello, easy-fs!";
+// 跨越多个块，用户堆只有16KB
+const LARGE_LEN: usize = 3000;
+// 用户地址空间的低处没有映射
+const BAD_ADDR: usize = 0x8;
+// 跳板页没有U位，用户不能让内核读写它
+const TRAMPOLINE: usize = usize::MAX - 0x1000 + 1;
+// 和内核中的MAX_FD保持一致
+const MAX_FD: usize = 64;
+
+#[no_mangle]
+fn main() -> i32 {
+    let fd = open(FILE_NAME, OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC);
+    assert!(fd >= 3, "open with CREATE failed: {}", fd);
+    let fd = fd as usize;
+    assert_eq!(write(fd, CONTENT), CONTENT.len() as isize);
+    let mut buf = [0u8; 64];
+    // 只写打开的文件不能读
+    assert_eq!(read(fd, &mut buf), -1);
+    assert_eq!(close(fd), 0);
+    assert_eq!(close(fd), -1);
+
+    let fd = open(FILE_NAME, OpenFlags::RDONLY);
+    assert!(fd >= 3);
+    let fd = fd as usize;
+    let mut stat = Stat::default();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.mode, StatMode::FILE);
+    assert_eq!(stat.size, CONTENT.len() as u64);
+    assert_eq!(read(fd, &mut buf), CONTENT.len() as isize);
+    assert_eq!(&buf[..CONTENT.len()], CONTENT);
+    // 读到末尾
+    assert_eq!(read(fd, &mut buf), 0);
+    assert_eq!(write(fd, CONTENT), -1);
+    assert_eq!(close(fd), 0);
+
+    // 关闭后文件描述符被复用，TRUNC清空原来的内容
+    let large = open(FILE_NAME, OpenFlags::RDWR | OpenFlags::TRUNC);
+    assert_eq!(large, fd as isize);
+    let data: Vec<u8> = (0..LARGE_LEN).map(|i| (i % 251) as u8).collect();
+    assert_eq!(write(fd, &data), LARGE_LEN as isize);
+    assert_eq!(close(fd), 0);
+    let fd = open(FILE_NAME, OpenFlags::RDONLY) as usize;
+    let mut back = vec![0u8; LARGE_LEN + 16];
+    assert_eq!(read(fd, &mut back), LARGE_LEN as isize);
+    assert!(back[..LARGE_LEN] == data[..]);
+    assert_eq!(close(fd), 0);
+
+    // 标准输入输出
+    assert_eq!(fstat(1, &mut stat), 0);
+    assert_eq!(stat.mode, StatMode::CHR);
+    assert_eq!(write(0, CONTENT), -1);
+    assert_eq!(read(1, &mut buf), -1);
+
+    // 无效的文件描述符和不存在的文件
+    assert_eq!(read(99, &mut buf), -1);
+    assert_eq!(write(99, CONTENT), -1);
+    assert_eq!(close(99), -1);
+    assert_eq!(fstat(99, &mut stat), -1);
+    assert_eq!(open("12file_missing\0", OpenFlags::RDONLY), -1);
+
+    // 只读打开时TRUNC不起作用
+    let fd = open(FILE_NAME, OpenFlags::RDONLY | OpenFlags::TRUNC) as usize;
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert_eq!(stat.size, LARGE_LEN as u64);
+    assert_eq!(close(fd), 0);
+
+    // 无效的用户地址返回-1而不是让内核panic，这些地址不能在用户态构造成引用，直接发起系统调用
+    let fd = open(FILE_NAME, OpenFlags::RDWR) as usize;
+    for addr in [BAD_ADDR, TRAMPOLINE] {
+        assert_eq!(syscall(SYSCALL_READ, [fd, addr, 16]), -1);
+        assert_eq!(syscall(SYSCALL_WRITE, [fd, addr, 16]), -1);
+        assert_eq!(syscall(SYSCALL_WRITE, [1, addr, 16]), -1);
+        assert_eq!(syscall(SYSCALL_OPEN, [addr, OpenFlags::RDONLY.bits() as usize, 0]), -1);
+        assert_eq!(syscall(SYSCALL_FSTAT, [fd, addr, 0]), -1);
+        assert_eq!(syscall(SYSCALL_PIPE, [addr, 0, 0]), -1);
+    }
+    assert_eq!(close(fd), 0);
+
+    // 文件描述符表是有上限的，满了之后open返回-1，关闭后可以再打开
+    let mut fds = Vec::new();
+    loop {
+        let fd = open(FILE_NAME, OpenFlags::RDONLY);
+        if fd < 0 {
+            break;
+        }
+        fds.push(fd as usize);
+    }
+    assert_eq!(fds.len(), MAX_FD - 3);
+    // 只剩一个空位时放不下管道的两端，已经分配的读端也会被释放
+    let last = fds.pop().unwrap();
+    assert_eq!(close(last), 0);
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), -1);
+    assert_eq!(open(FILE_NAME, OpenFlags::RDONLY), last as isize);
+    fds.push(last);
+    for &fd in fds.iter() {
+        assert_eq!(close(fd), 0);
+    }
+    assert_eq!(unlink(FILE_NAME), 0);
+
+    println!("file test passed!");
+    0
+}
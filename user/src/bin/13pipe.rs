@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exit, fork, fstat, pipe, read, waitpid, write, Stat, StatMode};
+
+// 比内核中的环形缓冲区长，读写双方都要等待对方
+const DATA_LEN: usize = 200;
+
+fn data() -> [u8; DATA_LEN] {
+    let mut data = [0u8; DATA_LEN];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = b'a' + (i % 26) as u8;
+    }
+    data
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let [read_end, write_end] = pipe_fd;
+    let mut stat = Stat::default();
+    assert_eq!(fstat(read_end, &mut stat), 0);
+    assert_eq!(stat.mode, StatMode::FIFO);
+    // 读端不能写，写端不能读
+    assert_eq!(write(read_end, b"x"), -1);
+    assert_eq!(read(write_end, &mut [0u8; 1]), -1);
+
+    let pid = fork();
+    if pid == 0 {
+        // 子进程关闭写端，否则读不到EOF
+        assert_eq!(close(write_end), 0);
+        let mut buf = [0u8; DATA_LEN];
+        let mut len = 0;
+        while len < DATA_LEN {
+            let n = read(read_end, &mut buf[len..]);
+            assert!(n > 0, "unexpected EOF after {} bytes", len);
+            len += n as usize;
+        }
+        assert!(buf == data());
+        // 所有写端都关闭后读到EOF
+        assert_eq!(read(read_end, &mut buf), 0);
+        assert_eq!(close(read_end), 0);
+        exit(0);
+    }
+    assert!(pid > 0, "fork failed");
+    assert_eq!(close(read_end), 0);
+    assert_eq!(write(write_end, &data()), DATA_LEN as isize);
+    assert_eq!(close(write_end), 0);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+
+    // 所有读端都关闭后写入失败
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    let [read_end, write_end] = pipe_fd;
+    assert_eq!(close(read_end), 0);
+    assert_eq!(write(write_end, b"x"), -1);
+    assert_eq!(close(write_end), 0);
+    println!("pipe test passed!");
+    0
+}
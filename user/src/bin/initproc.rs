@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exec, fork, wait, yield_};
+
+/// 内核启动的第一个进程：启动shell，之后一直回收孤儿进程
+///
+/// shell和所有孤儿进程都退出后，initproc也退出
+#[no_mangle]
+fn main() -> i32 {
+    if fork() == 0 {
+        exec("user_shell\0");
+        panic!("failed to exec user_shell!");
+    }
+    loop {
+        let mut exit_code: i32 = 0;
+        let pid = wait(&mut exit_code);
+        if pid == -1 {
+            // 没有子进程了
+            return 0;
+        }
+        println!(
+            "[initproc] Released a zombie process, pid={}, exit_code={}",
+            pid, exit_code,
+        );
+        yield_();
+    }
+}
@@ -0,0 +1,76 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+
+#[macro_use]
+extern crate user_lib;
+
+const LF: u8 = 0x0a;
+const CR: u8 = 0x0d;
+const DL: u8 = 0x7f;
+const BS: u8 = 0x08;
+const EOT: u8 = 0x04;
+
+use alloc::string::String;
+use user_lib::console::getchar;
+use user_lib::{exec, fork, waitpid};
+
+/// 读取一行命令，按名字运行对应的应用并打印它的退出码，输入exit或者Ctrl-D退出
+///
+/// 只要有一个应用以非0退出码结束，shell退出时返回1，initproc收到后内核以失败结束QEMU
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("Rust user shell");
+    let mut line: String = String::new();
+    let mut failed = false;
+    print!(">> ");
+    loop {
+        let c = getchar();
+        match c {
+            EOT if line.is_empty() => {
+                print!("{}", LF as char);
+                return failed as i32;
+            }
+            LF | CR => {
+                print!("{}", LF as char);
+                if line == "exit" {
+                    return failed as i32;
+                }
+                if !line.is_empty() {
+                    // exec需要以'\0'结尾的名字
+                    line.push('\0');
+                    let pid = fork();
+                    if pid == 0 {
+                        if exec(line.as_str()) == -1 {
+                            println!("Error when executing!");
+                            return -4;
+                        }
+                        unreachable!();
+                    } else {
+                        let mut exit_code: i32 = 0;
+                        let exit_pid = waitpid(pid as usize, &mut exit_code);
+                        assert_eq!(pid, exit_pid);
+                        println!("Shell: Process {} exited with code {}", pid, exit_code);
+                        failed |= exit_code != 0;
+                    }
+                    line.clear();
+                }
+                print!(">> ");
+            }
+            BS | DL => {
+                if !line.is_empty() {
+                    // 退格，用空格覆盖再退格
+                    print!("{}", BS as char);
+                    print!(" ");
+                    print!("{}", BS as char);
+                    line.pop();
+                }
+            }
+            _ => {
+                print!("{}", c as char);
+                line.push(c as char);
+            }
+        }
+    }
+}
@@ -11,5 +11,7 @@ fn panic_handler(panic_info: &core::panic::PanicInfo) -> ! {
     } else {
         println!("Panicked: {}", err);
     }
+    // 以非0退出码结束，父进程可以知道应用失败了
+    crate::exit(-1);
     loop {}
 }
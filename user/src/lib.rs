@@ -5,16 +5,40 @@
 // 具体来说，#![feature(linkage)] 允许使用 #[linkage = "..."] 这样的语法来指定函数或静态变量的链接属性。这样可以更灵活地控制代码的链接行为，例如将函数声明为 extern "C"，或者指定特定平台的链接属性等。
 // 需要注意的是，#![feature(linkage)] 是一个 unstable（不稳定）的功能，只能在使用 nightly 版本的 Rust 编译器时才能启用。
 #![feature(panic_info_message)]
+#![feature(alloc_error_handler)]
 
 #[macro_use]
 pub mod console;
 mod lang_items;
-mod syscall;
+/// 原始的系统调用，测试非法参数时绕过上层封装直接传地址
+pub mod syscall;
+
+extern crate alloc;
+
+use buddy_system_allocator::LockedHeap;
+
+/// 用户堆大小，供alloc中的String、Vec等使用
+const USER_HEAP_SIZE: usize = 16384;
+
+static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
+
+#[global_allocator]
+static HEAP: LockedHeap = LockedHeap::empty();
+
+#[alloc_error_handler]
+pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
+    panic!("Heap allocation error, layout = {:?}", layout);
+}
 
 #[no_mangle]
 #[link_section = ".text.entry"]
 pub extern "C" fn _start() -> ! {
     clear_bss();
+    // 堆在.bss中，需要在clear_bss之后初始化
+    unsafe {
+        HEAP.lock()
+            .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
+    }
     exit(main());
     panic!("unreachable after sys_exit!");
 }
@@ -38,8 +62,74 @@ fn clear_bss() {
     });
 }
 
+use bitflags::bitflags;
 use syscall::*;
 
+bitflags! {
+    /// open的flags，取值和Linux一致
+    pub struct OpenFlags: u32 {
+        const RDONLY = 0;
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        /// 文件不存在时创建
+        const CREATE = 1 << 6;
+        /// 打开时把文件清空
+        const TRUNC = 1 << 9;
+    }
+}
+bitflags! {
+    /// 文件类型
+    pub struct StatMode: u32 {
+        const NULL = 0;
+        /// 管道
+        const FIFO = 0o010000;
+        /// 字符设备
+        const CHR = 0o020000;
+        /// 目录
+        const DIR = 0o040000;
+        /// 普通文件
+        const FILE = 0o100000;
+    }
+}
+/// fstat返回的文件状态
+#[repr(C)]
+#[derive(Debug)]
+pub struct Stat {
+    pub mode: StatMode,
+    /// 文件大小（字节），不是普通文件时为0
+    pub size: u64,
+}
+impl Default for Stat {
+    fn default() -> Self {
+        Self {
+            mode: StatMode::NULL,
+            size: 0,
+        }
+    }
+}
+/// 打开根目录下名为`path`的文件，`path`需要以'\0'结尾，失败时返回-1
+pub fn open(path: &str, flags: OpenFlags) -> isize {
+    sys_open(path, flags.bits)
+}
+/// 删除根目录下名为`path`的文件，`path`需要以'\0'结尾，文件不存在或者还被打开着时返回-1
+pub fn unlink(path: &str) -> isize {
+    sys_unlinkat(path)
+}
+/// 关闭文件描述符，失败时返回-1
+pub fn close(fd: usize) -> isize {
+    sys_close(fd)
+}
+/// 读取`fd`对应文件的状态，失败时返回-1
+pub fn fstat(fd: usize, st: &mut Stat) -> isize {
+    sys_fstat(fd, st)
+}
+/// 创建管道，`pipe_fd[0]`为读端，`pipe_fd[1]`为写端，成功时返回0
+pub fn pipe(pipe_fd: &mut [usize; 2]) -> isize {
+    sys_pipe(pipe_fd)
+}
+pub fn read(fd: usize, buf: &mut [u8]) -> isize {
+    sys_read(fd, buf)
+}
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
@@ -49,6 +139,143 @@ pub fn exit(exit_code: i32) -> isize {
 pub fn yield_() -> isize {
     sys_yield()
 }
+/// 设置当前进程的调度优先级，数值越大得到的cpu时间越多
+pub fn set_priority(prio: isize) -> isize {
+    sys_set_priority(prio)
+}
+/// 进程`pid`在多级反馈队列中的层级，第0层优先级最高，其它调度策略下返回-1
+pub fn sched_level(pid: usize) -> isize {
+    sys_sched_level(pid)
+}
+/// 当前的Unix时间，以微秒为单位
 pub fn get_time() -> isize {
-    sys_get_time()
-}
\ No newline at end of file
+    let mut ts = TimeVal::default();
+    sys_get_time(&mut ts);
+    (ts.sec * 1_000_000 + ts.usec) as isize
+}
+/// 和Linux中的struct timeval一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+/// Unix时间
+pub const CLOCK_REALTIME: usize = 0;
+/// 开机以来的时间，不会被调整
+pub const CLOCK_MONOTONIC: usize = 1;
+/// 当前进程用掉的cpu时间
+pub const CLOCK_PROCESS_CPUTIME_ID: usize = 2;
+/// 每个进程只有一个线程，和CLOCK_PROCESS_CPUTIME_ID相同
+pub const CLOCK_THREAD_CPUTIME_ID: usize = 3;
+/// 读取`clock_id`对应的时钟，不支持的时钟返回-1
+pub fn clock_gettime(clock_id: usize, tp: &mut TimeSpec) -> isize {
+    sys_clock_gettime(clock_id, tp)
+}
+/// 和Linux中的struct timespec一致
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+/// 和内核中的系统调用号上限保持一致
+pub const MAX_SYSCALL_NUM: usize = 512;
+/// sys_task_info返回的统计信息，时间的单位都是微秒
+#[repr(C)]
+pub struct TaskInfo {
+    /// 第一次被调度的时间，还没有被调度过时为0
+    pub first_time: usize,
+    pub user_time: usize,
+    pub kernel_time: usize,
+    /// 被切换进来的次数
+    pub switch_count: usize,
+    /// 每个系统调用号被调用的次数
+    pub syscall_times: [u32; MAX_SYSCALL_NUM],
+}
+impl Default for TaskInfo {
+    fn default() -> Self {
+        Self {
+            first_time: 0,
+            user_time: 0,
+            kernel_time: 0,
+            switch_count: 0,
+            syscall_times: [0; MAX_SYSCALL_NUM],
+        }
+    }
+}
+/// 读取进程`pid`的统计信息，进程不存在时返回-1
+pub fn task_info(pid: usize, info: &mut TaskInfo) -> isize {
+    sys_task_info(pid, info)
+}
+/// 睡眠`ms`毫秒，期间不占用cpu
+pub fn sleep(ms: usize) -> isize {
+    sys_sleep(ms)
+}
+/// 按`req`睡眠，`rem`不为空时写入剩余时间（总是0）
+pub fn nanosleep(req: &TimeSpec, rem: Option<&mut TimeSpec>) -> isize {
+    sys_nanosleep(req, rem.map_or(core::ptr::null_mut(), |rem| rem as *mut _))
+}
+pub fn getpid() -> isize {
+    sys_getpid()
+}
+pub fn fork() -> isize {
+    sys_fork()
+}
+/// 按名字加载应用替换当前进程，`path`需要以'\0'结尾，成功时不会返回
+pub fn exec(path: &str) -> isize {
+    sys_exec(path)
+}
+pub fn mmap(start: usize, len: usize, prot: usize) -> isize {
+    sys_mmap(start, len, prot)
+}
+pub fn munmap(start: usize, len: usize) -> isize {
+    sys_munmap(start, len)
+}
+/// 把program break移动`size`字节，返回原来的program break，失败时返回-1
+pub fn sbrk(size: i32) -> isize {
+    sys_sbrk(size)
+}
+/// 等待任意一个子进程退出，子进程还没退出时让出cpu
+pub fn wait(exit_code: &mut i32) -> isize {
+    loop {
+        match sys_waitpid(-1, exit_code as *mut _) {
+            -2 => {
+                yield_();
+            }
+            // -1 or a real pid
+            exit_pid => return exit_pid,
+        }
+    }
+}
+/// 等待指定的子进程退出
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+    loop {
+        match sys_waitpid(pid as isize, exit_code as *mut _) {
+            -2 => {
+                yield_();
+            }
+            exit_pid => return exit_pid,
+        }
+    }
+}
+
+/// 应用触发非法访存被内核杀死时的退出码，和内核trap模块中的定义一致
+pub const EXIT_CODE_PAGE_FAULT: i32 = -2;
+/// 应用执行非法指令被内核杀死时的退出码
+pub const EXIT_CODE_ILLEGAL_INSTRUCTION: i32 = -3;
+/// 应用触发其他异常（断点、地址未对齐等）被内核杀死时的退出码
+pub const EXIT_CODE_EXCEPTION: i32 = -4;
+
+/// 在子进程中执行`f`并等待它结束，返回子进程的退出码；`f`应该让子进程被内核杀死，返回了就panic
+pub fn run_in_child(f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        panic!("child should have been killed!");
+    }
+    assert!(pid > 0, "fork failed");
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    exit_code
+}
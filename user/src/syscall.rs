@@ -1,14 +1,34 @@
 use core::arch::asm;
+use crate::{Stat, TaskInfo, TimeSpec, TimeVal};
 
-const SYSCALL_WRITE: usize = 64;
-const SYSCALL_EXIT: usize = 93;
-const SYSCALL_YIELD: usize = 124;
-const SYSCALL_GET_TIME: usize = 169;
+pub const SYSCALL_UNLINKAT: usize = 35;
+pub const SYSCALL_OPEN: usize = 56;
+pub const SYSCALL_CLOSE: usize = 57;
+pub const SYSCALL_PIPE: usize = 59;
+pub const SYSCALL_READ: usize = 63;
+pub const SYSCALL_WRITE: usize = 64;
+pub const SYSCALL_FSTAT: usize = 80;
+pub const SYSCALL_EXIT: usize = 93;
+pub const SYSCALL_NANOSLEEP: usize = 101;
+pub const SYSCALL_CLOCK_GETTIME: usize = 113;
+pub const SYSCALL_YIELD: usize = 124;
+pub const SYSCALL_SET_PRIORITY: usize = 140;
+pub const SYSCALL_GET_TIME: usize = 169;
+pub const SYSCALL_GETPID: usize = 172;
+pub const SYSCALL_SBRK: usize = 214;
+pub const SYSCALL_MUNMAP: usize = 215;
+pub const SYSCALL_FORK: usize = 220;
+pub const SYSCALL_EXEC: usize = 221;
+pub const SYSCALL_MMAP: usize = 222;
+pub const SYSCALL_WAITPID: usize = 260;
+pub const SYSCALL_TASK_INFO: usize = 410;
+pub const SYSCALL_SCHED_LEVEL: usize = 500;
+pub const SYSCALL_SLEEP: usize = 501;
 
 // s0 -> s11函数是保存寄存器
 // s0是sp寄存器，用于debugger
 // https://jborza.com/post/2021-05-11-riscv-linux-syscalls/
-fn syscall(id: usize, args: [usize; 3]) -> isize {
+pub fn syscall(id: usize, args: [usize; 3]) -> isize {
     let mut ret: isize;
     unsafe {
         // x10~x17 : 对应 a0~a7
@@ -26,6 +46,34 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     ret
 }
 
+// path需要以'\0'结尾，成功时返回文件描述符
+pub fn sys_open(path: &str, flags: u32) -> isize {
+    syscall(SYSCALL_OPEN, [path.as_ptr() as usize, flags as usize, 0])
+}
+
+// path需要以'\0'结尾
+pub fn sys_unlinkat(path: &str) -> isize {
+    syscall(SYSCALL_UNLINKAT, [path.as_ptr() as usize, 0, 0])
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    syscall(SYSCALL_CLOSE, [fd, 0, 0])
+}
+
+// 读端和写端的文件描述符依次写入pipe
+pub fn sys_pipe(pipe: &mut [usize; 2]) -> isize {
+    syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
+}
+
+pub fn sys_fstat(fd: usize, st: &mut Stat) -> isize {
+    syscall(SYSCALL_FSTAT, [fd, st as *mut _ as usize, 0])
+}
+
+// 没有输入时会阻塞，返回实际读到的字节数
+pub fn sys_read(fd: usize, buffer: &mut [u8]) -> isize {
+    syscall(SYSCALL_READ, [fd, buffer.as_mut_ptr() as usize, buffer.len()])
+}
+
 pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     // a0为字符串地址 这里的fd为1
     // a1 为字符串长度 
@@ -41,12 +89,69 @@ pub fn sys_yield() -> isize {
     syscall(SYSCALL_YIELD, [0, 0, 0])
 }
 
-// #[repr(C)]
-// pub struct TimeVal {
-//     pub sec: usize,
-//     pub usec: usize,
-// }
+pub fn sys_nanosleep(req: &TimeSpec, rem: *mut TimeSpec) -> isize {
+    syscall(SYSCALL_NANOSLEEP, [req as *const _ as usize, rem as usize, 0])
+}
+
+// 本内核自定义：睡眠ms毫秒
+pub fn sys_sleep(ms: usize) -> isize {
+    syscall(SYSCALL_SLEEP, [ms, 0, 0])
+}
+
+
+// prio至少为2，成功时返回prio，否则返回-1
+pub fn sys_set_priority(prio: isize) -> isize {
+    syscall(SYSCALL_SET_PRIORITY, [prio as usize, 0, 0])
+}
+
+// 不支持时区，tz总是传0
+pub fn sys_get_time(ts: &mut TimeVal) -> isize {
+    syscall(SYSCALL_GET_TIME, [ts as *mut _ as usize, 0, 0])
+}
+
+// 不支持的clock_id返回-1
+pub fn sys_clock_gettime(clock_id: usize, tp: &mut TimeSpec) -> isize {
+    syscall(SYSCALL_CLOCK_GETTIME, [clock_id, tp as *mut _ as usize, 0])
+}
+
+pub fn sys_getpid() -> isize {
+    syscall(SYSCALL_GETPID, [0, 0, 0])
+}
+
+pub fn sys_fork() -> isize {
+    syscall(SYSCALL_FORK, [0, 0, 0])
+}
+
+// path需要以'\0'结尾
+pub fn sys_exec(path: &str) -> isize {
+    syscall(SYSCALL_EXEC, [path.as_ptr() as usize, 0, 0])
+}
+
+// pid为-1时等待任意子进程，返回-2代表子进程还没退出
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
+    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, 0])
+}
+
+// pid不存在时返回-1
+pub fn sys_task_info(pid: usize, info: &mut TaskInfo) -> isize {
+    syscall(SYSCALL_TASK_INFO, [pid, info as *mut _ as usize, 0])
+}
+
+// prot的第0/1/2位分别表示可读/可写/可执行
+pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
+    syscall(SYSCALL_MMAP, [start, len, prot])
+}
+
+// 成功时返回原来的program break，失败时返回-1
+pub fn sys_sbrk(size: i32) -> isize {
+    syscall(SYSCALL_SBRK, [size as usize, 0, 0])
+}
+
+pub fn sys_munmap(start: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [start, len, 0])
+}
 
-pub fn sys_get_time() -> isize {
-    syscall(SYSCALL_GET_TIME, [0, 0, 0])
+// 诊断用：pid在多级反馈队列中的层级，不是多级反馈队列调度时返回-1
+pub fn sys_sched_level(pid: usize) -> isize {
+    syscall(SYSCALL_SCHED_LEVEL, [pid, 0, 0])
 }